@@ -0,0 +1,168 @@
+//! `capture!(path)`: a const from the caller's crate, copied into the script.
+//!
+//! Proc macros can't resolve names, so the const is found by reading the crate's source:
+//! `crate::` paths are followed from the crate root through its `mod` declarations,
+//! and other paths are looked up in the file the block is in.
+use std::path::Path;
+
+use quote::{quote, ToTokens};
+use syn::{visit::Visit, visit_mut::VisitMut, Expr, Item, UnOp};
+
+use super::{
+    modules::{current, module},
+    Nested,
+};
+
+fn is_capture(m: &syn::Macro) -> bool {
+    let path = m
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect::<Vec<_>>();
+    path == ["capture"] || path == ["edg", "capture"]
+}
+
+/// Replaces every `capture!(path)` in a block.
+pub struct Captures {
+    /// the block runs where it's written (`runtime-fallback`), so the path can be used as is
+    fallback: bool,
+    error: Option<String>,
+}
+
+impl Captures {
+    /// Substitute the captures in `body`.
+    pub fn apply(body: &mut Expr, fallback: bool) -> Result<(), String> {
+        let mut captures = Captures {
+            fallback,
+            error: None,
+        };
+        captures.visit_expr_mut(body);
+        captures.error.map_or(Ok(()), Err)
+    }
+
+    /// Uses of everything a block (or a block nested in it) captures, for the host:
+    /// they'd look unused otherwise, and rustc checks that they exist.
+    pub fn uses(body: &Expr) -> proc_macro2::TokenStream {
+        struct Paths(Vec<syn::Path>);
+        impl Visit<'_> for Paths {
+            fn visit_expr(&mut self, e: &Expr) {
+                if let Expr::Macro(m) = e {
+                    if is_capture(&m.mac) {
+                        self.0.extend(m.mac.parse_body().ok());
+                    } else if let Some(json) = Nested::kind(&m.mac) {
+                        if let Ok((_, block)) = super::block(m.mac.tokens.clone(), json) {
+                            self.visit_expr(&block.body);
+                        }
+                    }
+                }
+                syn::visit::visit_expr(self, e);
+            }
+        }
+        let mut paths = Paths(vec![]);
+        paths.visit_expr(body);
+        let paths = paths.0;
+        quote!(#(const _: () = { _ = &#paths; };)*)
+    }
+}
+
+impl VisitMut for Captures {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Macro(m) = e {
+            if is_capture(&m.mac) {
+                let captured = m
+                    .mac
+                    .parse_body::<syn::Path>()
+                    .map_err(|e| format!("bad capture!: {e}"))
+                    .and_then(|path| match self.fallback {
+                        true => Ok(syn::parse_quote!((#path))),
+                        false => resolve(&path),
+                    });
+                match captured {
+                    Ok(c) => *e = c,
+                    Err(err) => {
+                        self.error.get_or_insert(err);
+                    }
+                }
+                return;
+            }
+        }
+        syn::visit_mut::visit_expr_mut(self, e);
+    }
+}
+
+fn parse(file: &Path) -> Result<Vec<Item>, String> {
+    let src = std::fs::read_to_string(file)
+        .map_err(|e| format!("capture!: could not read {}: {e}", file.display()))?;
+    syn::parse_file(&src)
+        .map(|f| f.items)
+        .map_err(|e| format!("capture!: could not parse {}: {e}", file.display()))
+}
+
+/// Find `const NAME: T = <literal>;`, and make it an expression of type `T`.
+fn resolve(path: &syn::Path) -> Result<Expr, String> {
+    let shown = path.to_token_stream().to_string().replace(' ', "");
+    let mut segments = path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect::<Vec<_>>();
+    let (file, dir) = if segments.first().is_some_and(|s| s == "crate") {
+        segments.remove(0);
+        let root = super::args()
+            .into_iter()
+            .find(|a| a.ends_with(".rs"))
+            .ok_or("capture!: could not find the crate root")?;
+        module(std::env::current_dir().unwrap_or_default().join(root), true)
+    } else {
+        if segments.first().is_some_and(|s| s == "self") {
+            segments.remove(0);
+        }
+        current().ok_or("capture!: could not find the current file")?
+    };
+    let (name, modules) = segments.split_last().ok_or("capture!: empty path")?;
+
+    let mut items = parse(&file)?;
+    let mut dir = dir;
+    for m in modules {
+        let Some(found) = items.iter().find_map(|i| match i {
+            Item::Mod(x) if x.ident == m => Some(x),
+            _ => None,
+        }) else {
+            return Err(format!("capture!: no module `{m}` (in `{shown}`)"));
+        };
+        items = match &found.content {
+            Some((_, content)) => {
+                dir = dir.join(m);
+                content.clone()
+            }
+            None => {
+                let file = [dir.join(format!("{m}.rs")), dir.join(m).join("mod.rs")]
+                    .into_iter()
+                    .find(|f| f.exists())
+                    .ok_or_else(|| format!("capture!: could not find the file of module `{m}`"))?;
+                let (file, d) = module(file, false);
+                dir = d;
+                parse(&file)?
+            }
+        };
+    }
+    let Some(c) = items.iter().find_map(|i| match i {
+        Item::Const(c) if c.ident == name => Some(c),
+        _ => None,
+    }) else {
+        return Err(format!("capture!: no const `{shown}`"));
+    };
+    let literal = match &*c.expr {
+        Expr::Lit(_) => true,
+        Expr::Unary(u) => matches!(u.op, UnOp::Neg(_)) && matches!(*u.expr, Expr::Lit(_)),
+        _ => false,
+    };
+    if !literal {
+        return Err(format!(
+            "capture!: `{shown}` is not a literal, so it can't be copied into the script"
+        ));
+    }
+    let (ident, ty, expr) = (&c.ident, &c.ty, &c.expr);
+    Ok(syn::parse_quote!(({ const #ident: #ty = #expr; #ident })))
+}
@@ -0,0 +1,216 @@
+//! `edg::download!`: a file from the internet, fetched (with `curl`) at compile time.
+//!
+//! Downloads are cached outside of `target` (in `EDG_CACHE`, or `$CARGO_HOME/edg/downloads`), so clean builds don't
+//! fetch them again. Pinned ones are cached by their sha256, which they're checked against whenever they're used;
+//! unpinned ones by their url, until they're deleted from the cache.
+//! With `EDG_OFFLINE=1` (or `CARGO_NET_OFFLINE=true`), nothing is fetched, and downloads that aren't cached fail.
+use std::{path::PathBuf, process::Command};
+
+use quote::quote;
+use sha2::{Digest, Sha256};
+use syn::{
+    parse::{Parse, ParseStream},
+    LitStr, Token, Type,
+};
+
+use super::{flag, warning, OnError};
+
+struct Download {
+    url: LitStr,
+    ty: Option<Type>,
+    sha256: Option<LitStr>,
+}
+
+impl Parse for Download {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let url = input.parse()?;
+        let ty = match input.parse::<Option<Token![as]>>()? {
+            Some(_) => Some(input.parse()?),
+            None => None,
+        };
+        let mut sha256 = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<syn::Ident>()?;
+            if key != "sha256" {
+                return Err(syn::Error::new_spanned(key, "expected `sha256 = \"..\"`"));
+            }
+            input.parse::<Token![=]>()?;
+            sha256 = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(Self { url, ty, sha256 })
+    }
+}
+
+/// What the body is embedded as.
+enum Kind<'a> {
+    Bytes,
+    Str,
+    Json(&'a Type),
+}
+
+fn kind(ty: Option<&Type>) -> Kind<'_> {
+    let Some(ty) = ty else { return Kind::Bytes };
+    if let Type::Reference(r) = ty {
+        match &*r.elem {
+            Type::Path(p) if p.path.is_ident("str") => return Kind::Str,
+            Type::Slice(s) if matches!(&*s.elem, Type::Path(p) if p.path.is_ident("u8")) => {
+                return Kind::Bytes
+            }
+            _ => {}
+        }
+    }
+    Kind::Json(ty)
+}
+
+pub fn download(input: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let Download { url, ty, sha256 } = match syn::parse2(input) {
+        Ok(d) => d,
+        Err(e) => return e.to_compile_error(),
+    };
+    let pin = sha256.as_ref().map(|s| s.value().to_ascii_lowercase());
+    if let (Some(sha256), Some(pin)) = (&sha256, &pin) {
+        if pin.len() != 64 || !pin.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return syn::Error::new_spanned(sha256, "expected 64 hex digits").to_compile_error();
+        }
+    }
+    // nothing is fetched where blocks aren't evaluated: what's cached is used, and a placeholder otherwise
+    let unevaluated = cfg!(feature = "runtime-fallback") || super::metadata_only();
+    let (file, data, sha) = match fetch(&url.value(), pin.as_deref(), !unevaluated) {
+        Ok(f) => f,
+        Err(_) if unevaluated => return placeholder(ty.as_ref()),
+        Err(e) => return syn::Error::new_spanned(url, e).to_compile_error(),
+    };
+    let Some(path) = file.to_str() else {
+        let e = format!("{} is not utf8", file.display());
+        return syn::Error::new_spanned(url, e).to_compile_error();
+    };
+    let value = match kind(ty.as_ref()) {
+        Kind::Bytes => quote!({
+            const EDG: &[u8] = ::core::include_bytes!(#path);
+            EDG
+        }),
+        Kind::Str if std::str::from_utf8(&data).is_err() => {
+            return syn::Error::new_spanned(url, "the download is not utf8").to_compile_error()
+        }
+        Kind::Str => quote!(::core::include_str!(#path)),
+        Kind::Json(ty) => match serde_json::from_slice::<serde_json::Value>(&data) {
+            Ok(_) => OnError::Panic.decode(ty, "json", quote!(::core::include_str!(#path))),
+            Err(e) => {
+                let e = format!("the download is not json: {e}");
+                return syn::Error::new_spanned(url, e).to_compile_error();
+            }
+        },
+    };
+    let warning = match pin {
+        Some(_) => quote!(),
+        None => warning(&format!(
+            "edg: {} isn't pinned; add `sha256 = \"{sha}\"` to make sure it stays what it is",
+            url.value()
+        )),
+    };
+    quote!({ #warning #value })
+}
+
+/// An empty download (or `T`'s placeholder, see [`super::zero`]).
+fn placeholder(ty: Option<&Type>) -> proc_macro2::TokenStream {
+    match kind(ty) {
+        Kind::Bytes => quote!({
+            const EDG: &[u8] = &[];
+            EDG
+        }),
+        Kind::Str => quote!(""),
+        Kind::Json(ty) => {
+            super::zero(ty).unwrap_or_else(|| quote!(::edg::__private::placeholder::<#ty>()))
+        }
+    }
+}
+
+/// Where downloads are kept.
+fn cache() -> PathBuf {
+    if let Some(dir) = std::env::var_os("EDG_CACHE") {
+        return dir.into();
+    }
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+            Some(PathBuf::from(home).join(".cargo"))
+        })
+        .map_or_else(
+            || super::out_dir().join("downloads"),
+            |c| c.join("edg").join("downloads"),
+        )
+}
+
+fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The download (from the cache, if it's there, and the internet if it isn't and that's `online`):
+/// its file, its contents, and their sha256.
+fn fetch(url: &str, pin: Option<&str>, online: bool) -> Result<(PathBuf, Vec<u8>, String), String> {
+    let dir = cache();
+    let file = dir.join(match pin {
+        Some(pin) => format!("sha256-{pin}"),
+        // not `DefaultHasher`, which may hash differently after a toolchain update
+        None => format!("url-{}", sha256(url.as_bytes())),
+    });
+    if let Ok(data) = std::fs::read(&file) {
+        let sha = sha256(&data);
+        // a pinned file that doesn't match has been corrupted, and is fetched again
+        if pin.is_none_or(|p| p == sha) {
+            return Ok((file, data, sha));
+        }
+    }
+    if !online
+        || flag("EDG_OFFLINE")
+        || std::env::var("CARGO_NET_OFFLINE").is_ok_and(|v| v == "true")
+    {
+        return Err(format!(
+            "{url} isn't cached (in {}), and downloads are off",
+            dir.display()
+        ));
+    }
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("could not create {}: {e}", dir.display()))?;
+    // fetched next to where it goes, so it can be moved there whole
+    let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--retry",
+            "2",
+        ])
+        .arg("--output")
+        .arg(&tmp)
+        .arg(url)
+        .output()
+        .map_err(|e| format!("could not run curl: {e}"))?;
+    let data = match output.status.success() {
+        true => std::fs::read(&tmp).map_err(|e| format!("could not read {}: {e}", tmp.display())),
+        false => Err(format!(
+            "could not download {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    };
+    let data = data.inspect_err(|_| _ = std::fs::remove_file(&tmp))?;
+    let sha = sha256(&data);
+    if let Some(pin) = pin.filter(|&p| p != sha) {
+        _ = std::fs::remove_file(&tmp);
+        return Err(format!(
+            "{url} isn't what it was pinned to: its sha256 is {sha}, not {pin}"
+        ));
+    }
+    std::fs::rename(&tmp, &file).map_err(|e| {
+        _ = std::fs::remove_file(&tmp);
+        format!("could not write {}: {e}", file.display())
+    })?;
+    Ok((file, data, sha))
+}
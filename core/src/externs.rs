@@ -0,0 +1,59 @@
+//! The crates scripts are linked against: the ones the host was given with `--extern`.
+//!
+//! Cargo pipelines builds: a crate is started as soon as its dependencies' metadata (their `.rmeta`s) is written,
+//! while their rlibs are still being compiled. Scripts are binaries, which need the rlibs, so an extern that's an
+//! `.rmeta` is swapped for the `.rlib` next to it, which is waited for, until it's at least as new as the metadata
+//! (an older one is left over from an earlier build).
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How long the rlibs are waited for, before rustc is left to say which it couldn't find.
+pub const PATIENCE: Duration = Duration::from_secs(600);
+
+/// The `--extern`s to compile a script with, once the rlibs they name have been written.
+pub fn externs(args: &[String]) -> Vec<String> {
+    let deadline = Instant::now() + PATIENCE;
+    let mut ret = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg != "--extern" {
+            continue;
+        }
+        let Some(extern_) = args.next() else { break };
+        ret.push("--extern".into());
+        ret.push(match extern_.split_once('=') {
+            Some((name, path)) if path.ends_with(".rmeta") => {
+                match rlib(Path::new(path), deadline) {
+                    Some(rlib) => format!("{name}={}", rlib.display()),
+                    None => extern_.clone(),
+                }
+            }
+            _ => extern_.clone(),
+        });
+    }
+    ret
+}
+
+/// The rlib that goes with `rmeta`, once it's there.
+fn rlib(rmeta: &Path, deadline: Instant) -> Option<PathBuf> {
+    let rlib = rmeta.with_extension("rlib");
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    let metadata = modified(rmeta)?;
+    loop {
+        if modified(&rlib).is_some_and(|m| m >= metadata) {
+            return Some(rlib);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Did the script fail to link because a crate (a dependency of one of the externs, which isn't waited for)
+/// only had its metadata written?
+pub fn unlinked(stderr: &[u8]) -> bool {
+    String::from_utf8_lossy(stderr).contains("required to be available in rlib format")
+}
@@ -0,0 +1,156 @@
+//! What the crate being compiled is like, found from its root and the arguments rustc was given.
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+use syn::{parse::ParseStream, Attribute, Meta, NestedMeta};
+
+/// Is the crate `#![no_std]` (possibly through a `cfg_attr` that's on)?
+pub fn no_std() -> bool {
+    let Some(root) = super::args().into_iter().find(|a| a.ends_with(".rs")) else {
+        return false;
+    };
+    let Ok(src) = std::fs::read_to_string(root) else {
+        return false;
+    };
+    // only the inner attributes are of interest
+    let attrs = |input: ParseStream| {
+        let attrs = input.call(Attribute::parse_inner)?;
+        input.parse::<proc_macro2::TokenStream>()?;
+        Ok(attrs)
+    };
+    syn::parse::Parser::parse_str(attrs, &src).is_ok_and(|attrs| {
+        attrs
+            .iter()
+            .filter_map(|a| a.parse_meta().ok())
+            .any(|m| is_no_std(&m))
+    })
+}
+
+/// Is rustc only emitting metadata (for `cargo check`, or clippy)?
+pub fn metadata_only() -> bool {
+    let args = super::args();
+    let mut emit = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--emit=") {
+            Some(e) => emit.push(e),
+            None if arg == "--emit" => emit.extend(args.next().map(|e| &**e)),
+            None => {}
+        }
+    }
+    // `link` is the default, and may be `link=path`
+    !emit.is_empty()
+        && !emit
+            .iter()
+            .flat_map(|e| e.split(','))
+            .any(|e| e.split('=').next() == Some("link"))
+}
+
+fn is_no_std(meta: &Meta) -> bool {
+    match meta {
+        Meta::Path(p) => p.is_ident("no_std"),
+        Meta::List(l) if l.path.is_ident("cfg_attr") => {
+            let mut nested = l.nested.iter();
+            nested
+                .next()
+                .is_some_and(|p| matches!(p, NestedMeta::Meta(m) if cfg(m)))
+                && nested.any(|a| matches!(a, NestedMeta::Meta(m) if is_no_std(m)))
+        }
+        _ => false,
+    }
+}
+
+/// Evaluate a `cfg` predicate against what's set for the crate (as [`cfgs`] has it).
+pub fn cfg(meta: &Meta) -> bool {
+    let nested = |l: &syn::MetaList| {
+        l.nested
+            .iter()
+            .map(|n| matches!(n, NestedMeta::Meta(m) if cfg(m)))
+            .collect::<Vec<_>>()
+    };
+    match meta {
+        Meta::List(l) if l.path.is_ident("all") => nested(l).iter().all(|&b| b),
+        Meta::List(l) if l.path.is_ident("any") => nested(l).iter().any(|&b| b),
+        Meta::List(l) if l.path.is_ident("not") => nested(l) == [false],
+        Meta::Path(p) => set(p, None),
+        Meta::NameValue(nv) => match &nv.lit {
+            syn::Lit::Str(s) => set(&nv.path, Some(&s.value())),
+            _ => false,
+        },
+        Meta::List(_) => false,
+    }
+}
+
+fn set(path: &syn::Path, value: Option<&str>) -> bool {
+    let Some(name) = path.get_ident() else {
+        return false;
+    };
+    let cfg = match value {
+        Some(v) => format!("{name}={v:?}"),
+        None => name.to_string(),
+    };
+    cfgs().contains(&cfg)
+}
+
+/// `cfg`s, as rustc prints them.
+type Cfgs = Rc<HashSet<String>>;
+
+thread_local! {
+    /// [`cfgs`], for the arguments it was found with.
+    static CFGS: RefCell<Option<(Vec<String>, Cfgs)>> = const { RefCell::new(None) };
+}
+
+/// What's set for the crate, like `unix` or `target_pointer_width="64"`:
+/// `rustc --print cfg` for its target and codegen options (and `--cfg`s), with `test` under `--test`.
+/// Should that fail, only the `--cfg`s are known.
+fn cfgs() -> Cfgs {
+    let args = super::args();
+    if let Some(cfgs) =
+        CFGS.with_borrow(|c| c.as_ref().filter(|c| c.0 == args).map(|c| c.1.clone()))
+    {
+        return cfgs;
+    }
+    let mut forward = vec![];
+    let mut given = HashSet::new();
+    let mut it = args.iter().skip(1);
+    while let Some(arg) = it.next() {
+        match &**arg {
+            "--target" | "-C" | "--cfg" => {
+                let Some(value) = it.next() else { break };
+                if arg == "--cfg" {
+                    given.insert(value.clone());
+                }
+                forward.extend([arg.clone(), value.clone()]);
+            }
+            "-O" => forward.push(arg.clone()),
+            "--test" => _ = given.insert("test".into()),
+            _ if arg.starts_with("--target=") || arg.starts_with("-C") => forward.push(arg.clone()),
+            _ => {
+                if let Some(value) = arg.strip_prefix("--cfg=") {
+                    given.insert(value.into());
+                    forward.push(arg.clone());
+                }
+            }
+        }
+    }
+    let rustc = std::env::var_os("RUSTC").unwrap_or("rustc".into());
+    let printed = std::process::Command::new(rustc)
+        .args(["--print", "cfg"])
+        .args(forward)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        });
+    let cfgs = Rc::new(
+        given
+            .into_iter()
+            .chain(printed.into_iter().flatten())
+            .collect(),
+    );
+    CFGS.set(Some((args, Rc::clone(&cfgs))));
+    cfgs
+}
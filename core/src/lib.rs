@@ -0,0 +1,2993 @@
+//! What [edg](https://docs.rs/edg)'s macros expand to, and how: the blocks' scripts, and running them.
+//! Use edg instead; this is shared by `edg-macros`, and by `edg-test`, which runs blocks outside of rustc.
+
+extern crate proc_macro;
+
+/// `syn::parse_macro_input!`, for `proc_macro2`.
+macro_rules! parse {
+    ($input:ident as $ty:ty) => {
+        match syn::parse2::<$ty>($input) {
+            Ok(v) => v,
+            Err(e) => return e.to_compile_error(),
+        }
+    };
+}
+
+mod capture;
+mod download;
+mod externs;
+mod host;
+mod modules;
+mod pool;
+mod regex;
+mod speculate;
+mod stats;
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::OpenOptions,
+    hash::{Hash, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use capture::Captures;
+use modules::Modules;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    visit::Visit,
+    visit_mut::VisitMut,
+    Attribute, Expr, ExprClosure, Ident, ItemFn, Pat, ReturnType, Token, Type, TypeArray,
+    Visibility,
+};
+
+struct Input {
+    attrs: Vec<Attribute>,
+    closure: ExprClosure,
+    /// `as Type`
+    host: Option<Type>,
+    /// used (with a warning) when evaluation fails
+    fallback: Option<Expr>,
+    /// used when the block's `#![cfg]` is off
+    otherwise: Option<Expr>,
+    on_error: OnError,
+}
+
+/// What the expansion does when the result fails to deserialize at run time.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum OnError {
+    #[default]
+    Panic,
+    /// `T::default()`
+    Default,
+    /// evaluate to a `Result<T, edg::Error>`
+    Result,
+}
+
+impl Parse for OnError {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let policy = input.call(<Ident as syn::ext::IdentExt>::parse_any)?;
+        match &*policy.to_string() {
+            "panic" => Ok(Self::Panic),
+            "default" => Ok(Self::Default),
+            "result" => Ok(Self::Result),
+            _ => Err(syn::Error::new_spanned(
+                policy,
+                "expected `panic`, `default` or `result`",
+            )),
+        }
+    }
+}
+
+impl OnError {
+    /// Deserialize `arg` with `__private::from_{codec}` (or its fallible `try_` version), as the policy asks.
+    fn decode(
+        self,
+        ty: &Type,
+        codec: &str,
+        arg: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let (from, try_from) = (
+            quote::format_ident!("from_{codec}"),
+            quote::format_ident!("try_from_{codec}"),
+        );
+        match self {
+            Self::Panic => quote!(::edg::__private::#from::<#ty>(#arg)),
+            Self::Default => quote!(::core::result::Result::unwrap_or_default(
+                ::edg::__private::#try_from::<#ty>(#arg)
+            )),
+            Self::Result => quote!(::edg::__private::#try_from::<#ty>(#arg)),
+        }
+    }
+
+    /// A value that can't fail to deserialize, in the shape the policy asks for.
+    fn infallible(self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            Self::Result => quote!(::core::result::Result::Ok::<_, ::edg::Error>(#value)),
+            _ => value,
+        }
+    }
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        // `-> T { .. }` is short for `|| -> T { .. }`
+        let closure = match input.peek(Token![->]) {
+            true => {
+                let arrow = input.parse::<Token![->]>()?;
+                let ty = input.parse::<Type>()?;
+                let body = input.parse::<syn::Block>()?;
+                syn::parse_quote!(|| #arrow #ty #body)
+            }
+            false => input.parse()?,
+        };
+        let host = match input.parse::<Option<Token![as]>>()? {
+            Some(_) => Some(input.parse()?),
+            None => None,
+        };
+        let (mut fallback, mut otherwise, mut on_error) = (None, None, OnError::default());
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.call(<Ident as syn::ext::IdentExt>::parse_any)?;
+            input.parse::<Token![=]>()?;
+            match &*key.to_string() {
+                "fallback" => fallback = Some(input.parse()?),
+                "else" => otherwise = Some(input.parse()?),
+                "on_error" => on_error = input.parse()?,
+                _ => return Err(syn::Error::new_spanned(key, "unknown edg argument")),
+            }
+        }
+        Ok(Self {
+            attrs,
+            closure,
+            host,
+            fallback,
+            otherwise,
+            on_error,
+        })
+    }
+}
+
+struct Binding {
+    pat: Pat,
+    ty: Type,
+    closure: ExprClosure,
+}
+
+struct Bindings {
+    attrs: Vec<Attribute>,
+    bindings: Vec<Binding>,
+}
+
+impl Parse for Bindings {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let mut bindings = vec![];
+        while !input.is_empty() {
+            input.parse::<Token![let]>()?;
+            let pat = input.parse()?;
+            input.parse::<Token![:]>()?;
+            let ty = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let closure = input.parse()?;
+            input.parse::<Token![;]>()?;
+            bindings.push(Binding { pat, ty, closure });
+        }
+        Ok(Self { attrs, bindings })
+    }
+}
+
+struct Table {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    ty: TypeArray,
+    closure: ExprClosure,
+}
+
+impl Parse for Table {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let closure = input.parse()?;
+        input.parse::<Option<Token![;]>>()?;
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            ty,
+            closure,
+        })
+    }
+}
+
+struct Static {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    ty: Type,
+    closure: ExprClosure,
+}
+
+impl Parse for Static {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let closure = input.parse()?;
+        input.parse::<Option<Token![;]>>()?;
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            ty,
+            closure,
+        })
+    }
+}
+
+/// A closure body to evaluate.
+pub(crate) struct Block {
+    /// the type the script produces (inferred if absent)
+    pub(crate) ty: Option<Type>,
+    /// the type the host deserializes, if not `ty`
+    pub(crate) host: Option<Type>,
+    pub(crate) body: Expr,
+    /// used (with a warning) when evaluation fails
+    fallback: Option<Expr>,
+    /// used when the block's `#![cfg]` is off
+    otherwise: Option<Expr>,
+    /// the item of an iterator block, which the script collects, and which becomes a `&'static [T]`
+    slice: Option<Type>,
+    on_error: OnError,
+}
+
+/// Emits a warning by way of `#[deprecated]`, as stable has no better way.
+fn warning(msg: &str) -> proc_macro2::TokenStream {
+    quote!({
+        #[deprecated(note = #msg)]
+        struct EdgWarning;
+        _ = EdgWarning;
+    })
+}
+
+/// An item with a warning for each note, up to a point.
+fn warnings(notes: &[String]) -> proc_macro2::TokenStream {
+    if notes.is_empty() {
+        return quote!();
+    }
+    const MAX: usize = 16;
+    let mut out = notes
+        .iter()
+        .take(MAX)
+        .map(|n| warning(n))
+        .collect::<proc_macro2::TokenStream>();
+    if notes.len() > MAX {
+        out.extend(warning(&format!("edg: ... and {} more", notes.len() - MAX)));
+    }
+    quote!(const _: () = { #out };)
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    /// compile with rustc and run the binary
+    #[default]
+    Native,
+    Miri,
+    /// compile to `wasm32-wasip1` and run under wasmtime
+    Wasm,
+}
+
+#[derive(Default)]
+pub(crate) struct Options {
+    backend: Backend,
+    /// give the script `cfg(test)` when the host is a test build
+    test: bool,
+    /// the script prints an expression (through `ConstructTokens`) instead of json
+    construct: bool,
+    /// embed the result deflated
+    compress: bool,
+    /// a file piped into the script's stdin
+    stdin: Option<PathBuf>,
+    /// the rustup toolchain that compiles the script, if not the host's
+    toolchain: Option<String>,
+    /// the host is `#![no_std]`, so results have to be written out as literals
+    no_std: bool,
+    /// the block's `#![cfg(..)]` is off, so it isn't evaluated
+    disabled: bool,
+}
+
+fn flag(var: &str) -> bool {
+    std::env::var_os(var).is_some_and(|v| v != "0")
+}
+
+/// What `edg-test` runs blocks with, in place of the rustc a macro would be expanding in.
+pub struct Host {
+    /// rustc's arguments, crate root included
+    pub args: Vec<String>,
+    pub out_dir: PathBuf,
+}
+
+thread_local! {
+    pub static HOST: std::cell::RefCell<Option<Host>> = const { std::cell::RefCell::new(None) };
+}
+
+/// The arguments rustc was started with.
+fn args() -> Vec<String> {
+    HOST.with_borrow(|h| h.as_ref().map(|h| h.args.clone()))
+        .unwrap_or_else(|| std::env::args().collect())
+}
+
+/// `file:line` of the macro's call.
+fn at() -> String {
+    let line = match proc_macro::is_available() {
+        true => proc_macro::Span::call_site().line(),
+        false => 0,
+    };
+    format!("{}:{line}", call_site_file())
+}
+
+/// The file the macro was called from; under `edg-test`, the crate root.
+fn call_site() -> Option<PathBuf> {
+    match proc_macro::is_available() {
+        true => proc_macro::Span::call_site().local_file(),
+        false => args()
+            .into_iter()
+            .find(|a| a.ends_with(".rs"))
+            .map(PathBuf::from),
+    }
+}
+
+/// [`call_site`], as rustc shows it.
+fn call_site_file() -> String {
+    match proc_macro::is_available() {
+        true => proc_macro::Span::call_site().file(),
+        false => call_site().map_or_else(String::new, |f| f.display().to_string()),
+    }
+}
+
+impl Options {
+    fn new(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut o = Self::default();
+        if flag("EDG_MIRI") {
+            o.backend = Backend::Miri;
+        } else if flag("EDG_WASM") {
+            o.backend = Backend::Wasm;
+        }
+        o.test = flag("EDG_TEST");
+        o.compress = flag("EDG_COMPRESS");
+        o.toolchain = std::env::var("EDG_TOOLCHAIN")
+            .ok()
+            .filter(|t| !t.is_empty());
+        o.no_std = host::no_std();
+        for attr in attrs {
+            if attr.path.is_ident("miri") {
+                o.backend = Backend::Miri;
+            } else if attr.path.is_ident("wasm") {
+                o.backend = Backend::Wasm;
+            } else if attr.path.is_ident("test") {
+                o.test = true;
+            } else if attr.path.is_ident("construct") {
+                o.construct = true;
+            } else if attr.path.is_ident("compress") {
+                o.compress = true;
+            } else if attr.path.is_ident("stdin") {
+                o.stdin = Some(stdin(attr.parse_args()?)?);
+            } else if attr.path.is_ident("cfg") {
+                let syn::Meta::List(cfg) = attr.parse_meta()? else {
+                    return Err(syn::Error::new_spanned(attr, "expected `#![cfg(..)]`"));
+                };
+                let [syn::NestedMeta::Meta(predicate)] = &cfg.nested.iter().collect::<Vec<_>>()[..]
+                else {
+                    return Err(syn::Error::new_spanned(
+                        cfg.nested,
+                        "expected one predicate",
+                    ));
+                };
+                o.disabled |= !host::cfg(predicate);
+            } else if attr.path.is_ident("toolchain") {
+                o.toolchain = Some(attr.parse_args::<syn::LitStr>()?.value());
+            } else {
+                return Err(syn::Error::new_spanned(attr, "unknown edg attribute"));
+            }
+        }
+        Ok(o)
+    }
+
+    /// Items that make rustc rebuild when the block's inputs (`files`, and its stdin) change.
+    fn track(&self, files: &[PathBuf]) -> proc_macro2::TokenStream {
+        let path = self.stdin.iter().chain(files).filter_map(|p| p.to_str());
+        quote!(#(const _: &[u8] = ::core::include_bytes!(#path);)*)
+    }
+}
+
+/// Where `#![stdin(..)]` (or `edg::transform!`) reads from: a path relative to the crate's manifest,
+/// or an `include_str!`/`include_bytes!` of one relative to the current file.
+fn stdin(path: Expr) -> syn::Result<PathBuf> {
+    let (path, base) = match path {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(path),
+            ..
+        }) => (
+            path.value(),
+            std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from),
+        ),
+        Expr::Macro(m)
+            if m.mac.path.is_ident("include_str") || m.mac.path.is_ident("include_bytes") =>
+        {
+            let path = m.mac.parse_body::<syn::LitStr>()?.value();
+            (path, call_site().and_then(|f| Some(f.parent()?.to_owned())))
+        }
+        e => {
+            return Err(syn::Error::new_spanned(
+                e,
+                "expected a path, or an `include_str!`/`include_bytes!`",
+            ))
+        }
+    };
+    let cwd = std::env::current_dir().unwrap_or_default();
+    Ok(cwd.join(base.unwrap_or_default()).join(path))
+}
+
+/// A block's lock, which is released (and its temporary files removed) when dropped, even by a panic.
+struct Lock {
+    path: PathBuf,
+    temporaries: Vec<PathBuf>,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        for file in self.temporaries.iter().chain([&self.path]) {
+            _ = std::fs::remove_file(file);
+        }
+    }
+}
+
+/// How long a block's lock is waited for, before the build fails.
+const LOCK_PATIENCE: Duration = Duration::from_secs(600);
+
+/// Blocks are locked by their hash, so only identical ones (in this crate) wait on each other.
+fn lock(dir: &Path, hash: u64) -> Result<Lock, String> {
+    let deadline = Instant::now() + LOCK_PATIENCE;
+    let mut wait = Duration::from_millis(1);
+    loop {
+        if let Some(lock) = try_lock(dir, hash)? {
+            return Ok(lock);
+        }
+        if Instant::now() >= deadline {
+            let path = dir.join(format!("edg-{hash}.lock"));
+            let holder = std::fs::read_to_string(&path).unwrap_or_default();
+            return Err(format!(
+                "timed out waiting for {} (taken by process {}); if no build is running, delete it",
+                path.display(),
+                holder.trim(),
+            ));
+        }
+        std::thread::sleep(wait);
+        wait = (wait * 2).min(Duration::from_millis(100));
+    }
+}
+
+/// [`lock`], unless someone else has it.
+/// A lock whose process is gone (killed before it could release it) is taken over.
+fn try_lock(dir: &Path, hash: u64) -> Result<Option<Lock>, String> {
+    let path = dir.join(format!("edg-{hash}.lock"));
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                use std::io::Write;
+                _ = write!(file, "{}", std::process::id());
+                return Ok(Some(Lock {
+                    path,
+                    temporaries: vec![],
+                }));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                if !reclaim(&path) {
+                    return Ok(None);
+                }
+            }
+            Err(e) => return Err(format!("could not create {}: {e}", path.display())),
+        }
+    }
+    Ok(None)
+}
+
+/// Remove the lock at `path` if the process that took it is gone, returning whether it was.
+fn reclaim(path: &Path) -> bool {
+    let pid = |path: &Path| {
+        std::fs::read_to_string(path)
+            .ok()?
+            .trim()
+            .parse::<u32>()
+            .ok()
+    };
+    // an empty lock is one that was only just taken
+    let Some(holder) = pid(path).filter(|&pid| gone(pid)) else {
+        return false;
+    };
+    // moved out of the way first, so that a lock someone else took in the meantime can be put back
+    let stale = path.with_extension(format!("stale-{}", std::process::id()));
+    if std::fs::rename(path, &stale).is_err() {
+        return false;
+    }
+    if pid(&stale) != Some(holder) {
+        _ = std::fs::hard_link(&stale, path);
+    }
+    _ = std::fs::remove_file(&stale);
+    true
+}
+
+/// Has the process `pid` exited?
+fn gone(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        return !Path::new("/proc").join(pid.to_string()).exists();
+    }
+    let alive = match cfg!(windows) {
+        true => Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string())),
+        false => Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success()),
+    };
+    alive.is_ok_and(|alive| !alive)
+}
+
+/// `edg::r!`.
+pub fn r(input: TokenStream) -> TokenStream {
+    match block(input, false) {
+        Ok((options, block)) => expand(&options, block),
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+/// Run an `edg::r!` block, returning what it produced (its result's json, usually), or what it would fail with.
+pub fn eval(input: TokenStream) -> Result<String, String> {
+    let (options, block) = block(input, false).map_err(|e| e.to_string())?;
+    let infer = block.ty.is_none() && block.host.is_none();
+    let out = run(&options, block.ty.as_ref(), infer, &block.body)?;
+    // the inferred type's name comes first
+    Ok(match infer {
+        true => out
+            .out
+            .split_once('\n')
+            .map_or(out.out.clone(), |(_, v)| v.into()),
+        false => out.out,
+    })
+}
+
+/// Parse the input of `edg::r!`, or of `edg::json!`, which always produces a `serde_json::Value`.
+pub(crate) fn block(input: proc_macro2::TokenStream, json: bool) -> syn::Result<(Options, Block)> {
+    let Input {
+        attrs,
+        closure,
+        host,
+        fallback,
+        otherwise,
+        on_error,
+    } = syn::parse2(input)?;
+    let host = match (json, host) {
+        (true, Some(host)) => {
+            return Err(syn::Error::new_spanned(
+                host,
+                "edg::json! always produces a serde_json::Value",
+            ))
+        }
+        (true, None) => Some(syn::parse_quote!(::edg::__private::serde_json::Value)),
+        (false, host) => host,
+    };
+    let options = Options::new(&attrs)?;
+    let ty = match closure.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, t) => Some(*t),
+    };
+    let body = *closure.body;
+    // `impl Iterator<Item = T>`s are collected by the script
+    let (ty, body, slice) = match ty.as_ref().and_then(iterator) {
+        // `ConstructTokens` writes slices as `&[..]` already
+        Some(item) if options.construct => (
+            Some(syn::parse_quote!(&'static [#item])),
+            syn::parse_quote!(::std::vec::Vec::leak(
+                ::core::iter::Iterator::collect::<::std::vec::Vec<#item>>(#body)
+            )),
+            None,
+        ),
+        Some(item) => (
+            Some(syn::parse_quote!(::std::vec::Vec<#item>)),
+            syn::parse_quote!(::core::iter::Iterator::collect::<::std::vec::Vec<#item>>(#body)),
+            Some(item).filter(|_| host.is_none()),
+        ),
+        None => (ty, body, None),
+    };
+    let block = Block {
+        ty,
+        host,
+        body,
+        fallback,
+        otherwise,
+        slice,
+        on_error,
+    };
+    Ok((options, block))
+}
+
+/// `T`, of `impl Iterator<Item = T>`.
+fn iterator(ty: &Type) -> Option<Type> {
+    let Type::ImplTrait(t) = ty else { return None };
+    t.bounds.iter().find_map(|b| {
+        let syn::TypeParamBound::Trait(b) = b else {
+            return None;
+        };
+        let last = b.path.segments.last()?;
+        let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+            return None;
+        };
+        (last.ident == "Iterator").then_some(())?;
+        args.args.iter().find_map(|a| match a {
+            syn::GenericArgument::Binding(b) if b.ident == "Item" => Some(b.ty.clone()),
+            _ => None,
+        })
+    })
+}
+
+/// `edg::bind!`.
+pub fn bind(input: TokenStream) -> TokenStream {
+    let Bindings { attrs, bindings } = parse!(input as Bindings);
+    let options = match Options::new(&attrs) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+    bindings
+        .into_iter()
+        .map(|Binding { pat, ty, closure }| {
+            let value = expand(
+                &options,
+                Block {
+                    ty: Some(ty.clone()),
+                    host: None,
+                    body: *closure.body,
+                    fallback: None,
+                    otherwise: None,
+                    slice: None,
+                    on_error: OnError::Panic,
+                },
+            );
+            quote!(#[allow(non_snake_case)] let #pat: #ty = #value;)
+        })
+        .collect::<proc_macro2::TokenStream>()
+}
+
+/// `edg::json!`.
+pub fn json(input: TokenStream) -> TokenStream {
+    match block(input, true) {
+        Ok((options, block)) => expand(&options, block),
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+/// `edg::table!`.
+pub fn table(input: TokenStream) -> TokenStream {
+    let Table {
+        attrs,
+        vis,
+        name,
+        ty,
+        closure,
+    } = parse!(input as Table);
+    let options = match Options::new(&attrs) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+    let accessor = Ident::new(&name.to_string().to_lowercase(), name.span());
+    let TypeArray { elem, len, .. } = &ty;
+    let f = quote! {
+        #[inline]
+        #[allow(clippy::must_use_candidate, clippy::missing_panics_doc)]
+        #vis fn #accessor(i: usize) -> #elem {
+            #name[i]
+        }
+    };
+    if cfg!(feature = "runtime-fallback") {
+        return quote! {
+            #vis static #name: ::edg::__private::Lazy<#ty> = ::edg::__private::Lazy::new(|| ::core::array::from_fn(#closure));
+            #f
+        };
+    }
+
+    let body = syn::parse_quote!({
+        let f = #closure;
+        (0..#len).map(|i: usize| f(i)).collect::<::std::vec::Vec<#elem>>()
+    });
+    let vec = syn::parse_quote!(::std::vec::Vec<#elem>);
+    let table = run(&options, Some(&vec), false, &body).and_then(|e| {
+        let out = serde_json::from_str(&e.out).map_err(|e| e.to_string())?;
+        Ok((
+            literal(&out, &syn::parse_quote!([#elem]))?,
+            e.items(&options),
+        ))
+    });
+    match table {
+        Ok((table, items)) => quote! {
+            #vis static #name: #ty = #table;
+            #f
+            #items
+        },
+        Err(_) if metadata_only() => {
+            let zero = zero(&Type::Array(ty.clone()))
+                .unwrap_or_else(|| quote!(::edg::__private::placeholder::<#ty>()));
+            quote! {
+                #vis static #name: #ty = #zero;
+                #f
+            }
+        }
+        Err(e) => quote!(::core::compile_error!(#e);),
+    }
+}
+
+/// `edg::static_!`.
+pub fn static_(input: TokenStream) -> TokenStream {
+    let Static {
+        attrs,
+        vis,
+        name,
+        ty,
+        closure,
+    } = parse!(input as Static);
+    let mut options = match Options::new(&attrs) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+    if cfg!(feature = "runtime-fallback") {
+        return quote! {
+            #vis static #name: ::edg::__private::Lazy<#ty> = ::edg::__private::Lazy::new(#closure);
+        };
+    }
+    options.construct |= !literal_type(&ty);
+
+    let body = syn::parse_quote!((#closure)());
+    let value = run(&options, Some(&ty), false, &body).and_then(|e| {
+        let value = match options.construct {
+            true => e.out.parse().map_err(|err| {
+                format!(
+                    "`ConstructTokens` produced invalid tokens ({err}): {}",
+                    e.out
+                )
+            })?,
+            false => literal(
+                &serde_json::from_str(&e.out).map_err(|e| e.to_string())?,
+                &ty,
+            )?,
+        };
+        Ok((value, e.items(&options)))
+    });
+    match value {
+        Ok((value, items)) => quote! {
+            #vis static #name: #ty = #value;
+            #items
+        },
+        Err(_) if metadata_only() => {
+            let zero = zero(&ty).unwrap_or_else(|| quote!(::edg::__private::placeholder::<#ty>()));
+            quote!(#vis static #name: #ty = #zero;)
+        }
+        Err(e) => quote!(::core::compile_error!(#e);),
+    }
+}
+
+/// `edg::generate!`.
+pub fn generate(input: TokenStream) -> TokenStream {
+    let Input {
+        attrs,
+        closure,
+        host,
+        fallback,
+        otherwise,
+        on_error,
+    } = parse!(input as Input);
+    if on_error != OnError::Panic {
+        return quote!(::core::compile_error!(
+            "edg::generate! has nothing to deserialize, so it takes no `on_error`"
+        ));
+    }
+    if let Some(extra) = host
+        .map(ToTokens::into_token_stream)
+        .or(fallback.map(ToTokens::into_token_stream))
+        .or(otherwise.map(ToTokens::into_token_stream))
+    {
+        return syn::Error::new_spanned(extra, "edg::generate! takes only a closure")
+            .to_compile_error();
+    }
+    if cfg!(feature = "runtime-fallback") {
+        return quote!(::core::compile_error!("edg::generate! needs compile time evaluation, which the runtime-fallback feature disables"));
+    }
+    let options = match Options::new(&attrs) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    closure.to_token_stream().to_string().hash(&mut hasher);
+    let dir = out_dir().join(format!("edg-gen-{}", hasher.finish()));
+    let Some(dir) = dir.to_str() else {
+        return quote!(::core::compile_error!("target directory is not utf8"));
+    };
+    let body = syn::parse_quote!({
+        let dir = ::std::path::Path::new(#dir);
+        ::std::fs::create_dir_all(dir).expect("could not create output directory");
+        (#closure)(dir)
+    });
+    match run(&options, Some(&syn::parse_quote!(())), false, &body) {
+        // the path has to stay a bare literal (for `concat!`), so there's nowhere to put the block's warnings
+        // (nor anything to track its modules with)
+        Ok(_) => quote!(#dir),
+        // when only metadata is built, whatever an earlier build generated will have to do
+        Err(_) if metadata_only() => quote!(#dir),
+        Err(e) => quote!(::core::compile_error!(#e)),
+    }
+}
+
+struct Env {
+    name: syn::LitStr,
+    ty: Type,
+    check: Option<Expr>,
+}
+
+impl Parse for Env {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![as]>()?;
+        let ty = input.parse()?;
+        let check = match input.parse::<Option<Token![,]>>()? {
+            Some(_) if !input.is_empty() => Some(input.parse()?),
+            _ => None,
+        };
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { name, ty, check })
+    }
+}
+
+/// `edg::env!`.
+pub fn env(input: TokenStream) -> TokenStream {
+    let Env { name, ty, check } = parse!(input as Env);
+    let Ok(raw) = std::env::var(name.value()) else {
+        let msg = format!("environment variable `{}` is not set", name.value());
+        return syn::Error::new_spanned(name, msg).to_compile_error();
+    };
+    let check = check.map(|check| {
+        let src = check.to_token_stream().to_string();
+        quote! {
+            if let ::core::result::Result::Err(e) = ::edg::__private::Valid::valid((#check)(::core::clone::Clone::clone(&v))) {
+                ::core::panic!("`{}={:?}` is invalid ({}): {e}", #name, #raw, #src);
+            }
+        }
+    });
+    let body = syn::parse_quote!({
+        let v: #ty = match #raw.parse() {
+            ::core::result::Result::Ok(v) => v,
+            ::core::result::Result::Err(e) => ::core::panic!("`{}={:?}` is not a valid `{}`: {e}", #name, #raw, ::core::stringify!(#ty)),
+        };
+        #check
+        v
+    });
+    let options = match Options::new(&[]) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+    let value = expand(
+        &options,
+        Block {
+            ty: Some(ty),
+            host: None,
+            body,
+            fallback: None,
+            otherwise: None,
+            slice: None,
+            on_error: OnError::Panic,
+        },
+    );
+    // so that rustc rebuilds when the variable changes
+    quote!({
+        const _: ::core::option::Option<&str> = ::core::option_env!(#name);
+        #value
+    })
+}
+
+/// `edg::buildinfo!`.
+pub fn buildinfo(input: TokenStream) -> TokenStream {
+    parse!(input as syn::parse::Nothing);
+    let args = args();
+    let mut features = args
+        .windows(2)
+        .filter(|w| w[0] == "--cfg")
+        .filter_map(|w| w[1].strip_prefix("feature=\"")?.strip_suffix('"'))
+        .collect::<Vec<_>>();
+    features.sort_unstable();
+    let body = syn::parse_quote!({
+        let run = |program: &::std::ffi::OsStr, args: &[&str]| {
+            ::std::process::Command::new(program)
+                .args(args)
+                .current_dir(::std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or(".".into()))
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .and_then(|o| ::std::string::String::from_utf8(o.stdout).ok())
+        };
+        let commit = run("git".as_ref(), &["rev-parse", "HEAD"]).map(|c| c.trim().to_owned());
+        let dirty = commit.is_some()
+            && run("git".as_ref(), &["status", "--porcelain", "--untracked-files=no"]).is_some_and(|s| !s.trim().is_empty());
+        let rustc = ::std::env::var_os("RUSTC").unwrap_or("rustc".into());
+        let version = run(&rustc, &["-vV"]).unwrap_or_default();
+        let host = version
+            .lines()
+            .find_map(|l| l.strip_prefix("host: "))
+            .unwrap_or("unknown")
+            .to_owned();
+        let timestamp = ::std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or_else(|| {
+                ::std::time::SystemTime::now()
+                    .duration_since(::std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs())
+            });
+        ::edg::BuildInfo {
+            commit: commit.map(::std::string::String::leak).map(|c| &*c),
+            dirty,
+            timestamp,
+            rustc: ::std::string::String::leak(
+                version.lines().next().unwrap_or("rustc (unknown)").to_owned(),
+            ),
+            host: ::std::string::String::leak(host),
+            features: &[#(#features),*],
+        }
+    });
+    let options = Options {
+        construct: true,
+        ..match Options::new(&[]) {
+            Ok(o) => o,
+            Err(e) => return e.to_compile_error(),
+        }
+    };
+    let value = expand(
+        &options,
+        Block {
+            ty: Some(syn::parse_quote!(::edg::BuildInfo)),
+            host: None,
+            body,
+            fallback: None,
+            otherwise: None,
+            slice: None,
+            on_error: OnError::Panic,
+        },
+    );
+    // so that rustc rebuilds when the commit (or the epoch) changes
+    let git = git_files();
+    quote!({
+        #(const _: &[u8] = ::core::include_bytes!(#git);)*
+        const _: ::core::option::Option<&str> = ::core::option_env!("SOURCE_DATE_EPOCH");
+        #value
+    })
+}
+
+/// The files that say which commit the crate's repository is at: its `HEAD`, the branch that points to,
+/// `packed-refs` (where branches end up after a `git gc`), and `HEAD`'s log (as committing to a packed branch
+/// only adds a branch file, which isn't one of these).
+/// In a worktree or a submodule, `.git` is a file naming the repository's directory, and branches are in the
+/// directory `commondir` names (the main one's).
+fn git_files() -> Vec<String> {
+    let manifest = std::env::var_os("CARGO_MANIFEST_DIR").map_or_else(
+        || std::env::current_dir().unwrap_or_default(),
+        PathBuf::from,
+    );
+    let Some(git) = manifest.ancestors().find_map(|dir| {
+        let git = dir.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        let file = std::fs::read_to_string(&git).ok()?;
+        Some(dir.join(file.strip_prefix("gitdir:")?.trim())).filter(|g| g.is_dir())
+    }) else {
+        return vec![];
+    };
+    let common = std::fs::read_to_string(git.join("commondir"))
+        .map_or_else(|_| git.clone(), |c| git.join(c.trim()));
+    let head = git.join("HEAD");
+    let branch = std::fs::read_to_string(&head)
+        .ok()
+        .and_then(|h| Some(h.strip_prefix("ref: ")?.trim().to_owned()))
+        .and_then(|b| {
+            [git.join(&b), common.join(&b)]
+                .into_iter()
+                .find(|b| b.is_file())
+        });
+    [head]
+        .into_iter()
+        .chain(branch)
+        .chain(
+            [common.join("packed-refs"), git.join("logs").join("HEAD")]
+                .into_iter()
+                .filter(|f| f.is_file()),
+        )
+        .filter_map(|f| f.to_str().map(str::to_owned))
+        .collect()
+}
+
+/// `edg::download!`.
+pub fn download(input: TokenStream) -> TokenStream {
+    download::download(input)
+}
+
+/// `edg::regex!`.
+pub fn regex(input: TokenStream) -> TokenStream {
+    regex::regex(input)
+}
+
+struct Transform {
+    attrs: Vec<Attribute>,
+    path: Expr,
+    closure: ExprClosure,
+}
+
+impl Parse for Transform {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let path = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let closure = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self {
+            attrs,
+            path,
+            closure,
+        })
+    }
+}
+
+/// `edg::transform!`.
+pub fn transform(input: TokenStream) -> TokenStream {
+    let Transform {
+        attrs,
+        path,
+        closure,
+    } = parse!(input as Transform);
+    let mut options = match Options::new(&attrs) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+    if options.stdin.is_some() {
+        return quote!(::core::compile_error!(
+            "edg::transform! already gives the block a stdin"
+        ));
+    }
+    let file = match stdin(path) {
+        Ok(file) => file,
+        Err(e) => return e.to_compile_error(),
+    };
+    let bytes = match closure.inputs.first() {
+        Some(Pat::Type(t)) => t.ty.to_token_stream().to_string().replace(' ', "") == "&[u8]",
+        _ => false,
+    };
+    let ty = match &closure.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, t) => Some((**t).clone()),
+    };
+    let body = match (cfg!(feature = "runtime-fallback"), file.to_str()) {
+        // nothing is piped in at runtime
+        (true, Some(file)) if bytes => syn::parse_quote!((#closure)(::core::include_bytes!(#file))),
+        (true, Some(file)) => syn::parse_quote!((#closure)(::core::include_str!(#file))),
+        (true, None) => return quote!(::core::compile_error!("the path is not utf8")),
+        (false, _) if bytes => syn::parse_quote!({
+            let mut input = ::std::vec::Vec::new();
+            ::std::io::Read::read_to_end(&mut ::std::io::stdin(), &mut input).expect("could not read the file");
+            (#closure)(&input)
+        }),
+        (false, _) => syn::parse_quote!({
+            let input = ::std::io::read_to_string(::std::io::stdin()).expect("the file is not utf8");
+            (#closure)(&input)
+        }),
+    };
+    // at runtime, it's included instead
+    if !cfg!(feature = "runtime-fallback") {
+        options.stdin = Some(file);
+    }
+    expand(
+        &options,
+        Block {
+            ty,
+            host: None,
+            body,
+            fallback: None,
+            otherwise: None,
+            slice: None,
+            on_error: OnError::Panic,
+        },
+    )
+}
+
+struct Assert {
+    attrs: Vec<Attribute>,
+    closure: ExprClosure,
+    msg: Option<syn::LitStr>,
+}
+
+impl Parse for Assert {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let closure = input.parse()?;
+        let msg = match input.parse::<Option<Token![,]>>()? {
+            Some(_) if !input.is_empty() => Some(input.parse()?),
+            _ => None,
+        };
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self {
+            attrs,
+            closure,
+            msg,
+        })
+    }
+}
+
+/// `edg::assert!`.
+pub fn assert(input: TokenStream) -> TokenStream {
+    let Assert {
+        attrs,
+        closure,
+        msg,
+    } = parse!(input as Assert);
+    if cfg!(feature = "runtime-fallback") {
+        return TokenStream::new();
+    }
+    let options = match Options::new(&attrs) {
+        Ok(o) => o,
+        Err(e) => return e.to_compile_error(),
+    };
+    let body = syn::parse_quote!(::edg::__private::Valid::valid((#closure)()));
+    let ty = syn::parse_quote!(::core::result::Result<(), ::std::string::String>);
+    let failure = match run(&options, Some(&ty), false, &body) {
+        Ok(e) => match serde_json::from_str(&e.out) {
+            Ok(serde_json::Value::Object(o)) if o.contains_key("Ok") => {
+                return e.items(&options);
+            }
+            Ok(serde_json::Value::Object(mut o)) => match o.remove("Err") {
+                Some(serde_json::Value::String(e)) => e,
+                _ => format!("bad output: {}", e.out),
+            },
+            _ => format!("bad output: {}", e.out),
+        },
+        Err(_) if metadata_only() => return TokenStream::new(),
+        Err(e) => e,
+    };
+    let msg = match msg {
+        Some(msg) => format!("{}: {failure}", msg.value()),
+        None => format!(
+            "edg::assert!({}) failed: {failure}",
+            closure.to_token_stream()
+        ),
+    };
+    syn::Error::new_spanned(closure, msg).to_compile_error()
+}
+
+/// `#[edg::memo]`.
+pub fn memo(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let inputs = parse!(attr as Expr);
+    let f = parse!(item as ItemFn);
+    match memo_impl(&inputs, &f) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let e = e.to_compile_error();
+            // keep the function, so that its callers don't error too
+            quote!(#e #f)
+        }
+    }
+}
+
+fn memo_impl(inputs: &Expr, f: &ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = f;
+    if !sig.generics.params.is_empty() || sig.asyncness.is_some() {
+        return Err(syn::Error::new_spanned(
+            sig,
+            "edg::memo needs a plain function to evaluate",
+        ));
+    }
+    let ReturnType::Type(_, ret) = &sig.output else {
+        return Err(syn::Error::new_spanned(
+            sig,
+            "edg::memo needs a return value",
+        ));
+    };
+    let args = sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            syn::FnArg::Typed(t) => Ok(&*t.ty),
+            syn::FnArg::Receiver(r) => Err(syn::Error::new_spanned(r, "edg::memo can't take self")),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let ident = &sig.ident;
+    let names = (0..args.len())
+        .map(|i| quote::format_ident!("arg{i}"))
+        .collect::<Vec<_>>();
+    // the arguments, as one value
+    let (arg, call): (Type, _) = match &args[..] {
+        [ty] => ((*ty).clone(), quote!(#ident(x.clone()))),
+        _ => {
+            let i = (0..args.len()).map(syn::Index::from);
+            (
+                syn::parse_quote!((#(#args),*)),
+                quote!(#ident(#(x.#i.clone()),*)),
+            )
+        }
+    };
+    // the original, for inputs that weren't precomputed (and for recursion)
+    let original = ItemFn {
+        attrs: vec![],
+        vis: Visibility::Inherited,
+        sig: sig.clone(),
+        block: block.clone(),
+    };
+    let outer = syn::Signature {
+        inputs: syn::parse_quote!(#(#names: #args),*),
+        ..sig.clone()
+    };
+    let scrutinee = match &names[..] {
+        [name] => quote!(#name),
+        _ => quote!((#(#names),*)),
+    };
+    if cfg!(feature = "runtime-fallback") {
+        return Ok(quote!(#f));
+    }
+
+    let body = syn::parse_quote!({
+        #original
+        (#inputs).into_iter().map(|x: #arg| { let y = #call; (x, y) }).collect::<::std::vec::Vec<(#arg, #ret)>>()
+    });
+    let ty = syn::parse_quote!(::std::vec::Vec<(#arg, #ret)>);
+    let options = Options::new(&[])?;
+    let arms = run(&options, Some(&ty), false, &body).and_then(|e| {
+        let out: Vec<(serde_json::Value, serde_json::Value)> =
+            serde_json::from_str(&e.out).map_err(|e| e.to_string())?;
+        let arms = out
+            .iter()
+            .map(|(x, y)| Ok((literal(x, &arg)?, literal(y, ret)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok((arms, e.items(&options)))
+    });
+    let (arms, items) = match arms {
+        Ok(arms) => arms,
+        // when only metadata is built, the original will do
+        Err(_) if metadata_only() => return Ok(quote!(#f)),
+        Err(e) => return Err(syn::Error::new_spanned(inputs, e)),
+    };
+    let (pats, vals): (Vec<_>, Vec<_>) = arms.into_iter().unzip();
+    Ok(quote! {
+        #(#attrs)*
+        #vis #outer {
+            #items
+            #original
+            #[allow(unreachable_patterns, clippy::match_same_arms)]
+            match #scrutinee {
+                #(#pats => #vals,)*
+                _ => #ident(#(#names),*),
+            }
+        }
+    })
+}
+
+/// Write a json value out as a literal of type `ty`.
+fn literal(v: &serde_json::Value, ty: &Type) -> Result<proc_macro2::TokenStream, String> {
+    use serde_json::Value;
+    Ok(match v {
+        Value::Bool(b) => quote!(#b),
+        // typed, so that they don't fall back to `i32`/`f64`
+        Value::Number(n) => format!("{n}{}", number(ty).unwrap_or_default())
+            .parse()
+            .map_err(|e| format!("bad number {n}: {e}"))?,
+        Value::String(s) if number(ty).is_some() => stand_in(ty, s)
+            .ok_or_else(|| format!("cannot write {s:?} as a {}", ty.to_token_stream()))?,
+        Value::String(s) if ty.to_token_stream().to_string() == "char" => {
+            let c = s.chars().next().ok_or("empty char")?;
+            quote!(#c)
+        }
+        Value::String(s) => quote!(#s),
+        Value::Array(a) => {
+            let elem: Vec<&Type> = match ty {
+                Type::Array(t) => vec![&t.elem; a.len()],
+                Type::Slice(t) => vec![&t.elem; a.len()],
+                Type::Tuple(t) if t.elems.len() == a.len() => t.elems.iter().collect(),
+                Type::Reference(r) => return literal(v, &r.elem).map(|l| quote!(&#l)),
+                Type::Paren(p) => return literal(v, &p.elem),
+                _ => {
+                    return Err(format!(
+                        "cannot write {} as a literal",
+                        ty.to_token_stream()
+                    ))
+                }
+            };
+            let items = a
+                .iter()
+                .zip(elem)
+                .map(|(v, t)| literal(v, t))
+                .collect::<Result<Vec<_>, _>>()?;
+            match ty {
+                Type::Tuple(_) => quote!((#(#items,)*)),
+                _ => quote!([#(#items),*]),
+            }
+        }
+        Value::Null | Value::Object(_) => {
+            return Err(format!("cannot write {v} as a literal"));
+        }
+    })
+}
+
+/// Evaluate `body` and emit the expression that produces its value.
+fn expand(
+    options: &Options,
+    Block {
+        ty,
+        host,
+        body,
+        fallback,
+        otherwise,
+        slice,
+        on_error,
+    }: Block,
+) -> proc_macro2::TokenStream {
+    if options.disabled {
+        let slice = slice.map(|item| syn::parse_quote!(&'static [#item]));
+        return match otherwise {
+            // it stands in for the result, so it's of the same type
+            Some(otherwise) => {
+                on_error.infallible(match slice.as_ref().or(host.as_ref()).or(ty.as_ref()) {
+                    Some(ty) => quote!({ let edg: #ty = #otherwise; edg }),
+                    None => quote!((#otherwise)),
+                })
+            }
+            None => quote!(::core::compile_error!(
+                "this block's `#![cfg]` is off, so it needs an `else = ..` to use instead"
+            )),
+        };
+    }
+    if let Some(host) = host.as_ref().filter(|_| options.construct) {
+        return syn::Error::new_spanned(
+            host,
+            "`#![construct]` blocks can't be converted with `as`",
+        )
+        .to_compile_error();
+    }
+    // neither type is known: ask the script
+    let infer = ty.is_none() && host.is_none();
+    if cfg!(feature = "runtime-fallback") {
+        let mut body = body;
+        if let Err(e) = Captures::apply(&mut body, true) {
+            return quote!(::core::compile_error!(#e));
+        }
+        if options.stdin.is_some() {
+            return quote!(::core::compile_error!(
+                "`#![stdin(..)]` needs compile time evaluation, which the runtime-fallback feature disables"
+            ));
+        }
+        let block = match (&ty, &body) {
+            (Some(ty), Expr::Block(_)) => quote!(|| -> #ty #body),
+            (Some(ty), _) => quote!(|| -> #ty { #body }),
+            (None, _) => quote!(|| #body),
+        };
+        // as in the script, which allows them too
+        let block = quote!({
+            #[allow(unused_parens, unused_braces)]
+            let edg = #block;
+            edg
+        });
+        let value = match (&host, slice) {
+            // go through serde, as it would have at compile time
+            (Some(host), _) => on_error.decode(
+                host,
+                "json",
+                quote!(&::edg::__private::to_json(&::edg::__private::at_run_time(#block))),
+            ),
+            (None, Some(item)) => on_error.infallible(quote!({
+                static EDG: ::std::sync::LazyLock<::std::vec::Vec<#item>> = ::std::sync::LazyLock::new(#block);
+                ::edg::__private::at_run_time(|| ::std::vec::Vec::as_slice(&EDG))
+            })),
+            (None, None) => on_error.infallible(quote!(::edg::__private::at_run_time(#block))),
+        };
+        return match fallback {
+            Some(fallback) => {
+                let fallback = on_error.infallible(fallback.to_token_stream());
+                quote!(::edg::__private::at_run_time_or(|| #value, || #fallback))
+            }
+            None => value,
+        };
+    }
+    // the type of the expansion
+    let known = match &slice {
+        Some(item) => Some(syn::parse_quote!(&'static [#item])),
+        None => host.clone().or(ty.clone()),
+    };
+    let returned = ty.clone();
+    // blocks nested in another end up in its script, where the captured paths mean nothing
+    let uses = match NESTED.get() {
+        true => quote!(),
+        false => Captures::uses(&body),
+    };
+    let result = if options.construct {
+        // the script wrote the expression for us
+        run(options, ty.as_ref(), false, &body).and_then(|e| {
+            let tokens = e.out.parse::<proc_macro2::TokenStream>().map_err(|err| {
+                format!(
+                    "`ConstructTokens` produced invalid tokens ({err}): {}",
+                    e.out
+                )
+            })?;
+            Ok((on_error.infallible(tokens), e.items(options)))
+        })
+    } else {
+        run(options, ty.as_ref(), infer, &body)
+            .and_then(|e| match host.or(ty) {
+                Some(host) => Ok((host, e.out.clone(), e.items(options))),
+                None => {
+                    let (name, out) = e.out.split_once('\n').unwrap_or_default();
+                    Ok((infer_type(name)?, out.to_owned(), e.items(options)))
+                }
+            })
+            .and_then(|(host, comptime_expr, items)| {
+                if let Some(item) = &slice {
+                    return Ok((
+                        static_slice(item, &comptime_expr, options.no_std, on_error)?,
+                        items,
+                    ));
+                }
+                let tokens = match scalar(&host, &comptime_expr) {
+                    Some(tokens) => on_error.infallible(tokens),
+                    // there's nothing to deserialize with
+                    None if options.no_std => serde_json::from_str(&comptime_expr)
+                        .map_err(|e| e.to_string())
+                        .and_then(|v| literal(&v, &host))
+                        .map(|lit| on_error.infallible(lit))
+                        .map_err(|e| format!("in `#![no_std]` crates, results are written out as literals, so they can only be made of primitives, arrays, tuples and `&'static str`s (or be `#![construct]`ed): {e}"))?,
+                    None => allowed(
+                        compressed(&host, &comptime_expr, on_error)
+                            .filter(|_| options.compress)
+                            .or_else(|| {
+                                sidecar(&host, &comptime_expr).map(|s| on_error.infallible(s))
+                            })
+                            .unwrap_or_else(|| {
+                                on_error.decode(&host, "json", quote!(#comptime_expr))
+                            }),
+                    ),
+                };
+                Ok((tokens, items))
+            })
+    };
+    match result {
+        Ok((tokens, items)) if !items.is_empty() || !uses.is_empty() => {
+            quote!({ #items #uses #tokens })
+        }
+        Ok((tokens, _)) => tokens,
+        Err(compile_error) => match fallback {
+            Some(fallback) => {
+                let warning = warning(&format!(
+                    "edg: evaluation failed, using the fallback instead: {compile_error}"
+                ));
+                on_error.infallible(quote!({ #warning #fallback }))
+            }
+            // keep docs (and checks) building; the value doesn't matter there, unless it's in a const
+            None if metadata_only() => match known {
+                Some(ty) => on_error.infallible(
+                    zero(&ty).unwrap_or_else(|| quote!(::edg::__private::placeholder::<#ty>())),
+                ),
+                // the closure's type is the block's, so it's checked where it is (and not called)
+                None if !options.no_std => {
+                    let mut body = body;
+                    match Captures::apply(&mut body, true) {
+                        Ok(()) => on_error.infallible(allowed(quote!(
+                            ::edg::__private::placeholder_of(|| #body)
+                        ))),
+                        Err(_) => quote!(::core::panic!(#NOT_EVALUATED)),
+                    }
+                }
+                None => quote!(::core::panic!(#NOT_EVALUATED)),
+            },
+            None => match returned.filter(|_| compile_error.starts_with(UNSERIALIZABLE)) {
+                Some(ty) => syn::Error::new_spanned(ty, compile_error).to_compile_error(),
+                None => quote!(::core::compile_error!(#compile_error)),
+            },
+        },
+    }
+}
+
+/// The deserialization of a result, in a block that allows the lints it could set off
+/// (and that crates denying `clippy::pedantic` would otherwise fail on).
+fn allowed(expr: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    quote!({
+        #[allow(clippy::all, clippy::pedantic, clippy::nursery)]
+        let edg = #expr;
+        edg
+    })
+}
+
+/// An iterator block's result, as a `&'static [T]`: a literal one if `T` is made of primitives,
+/// or a `static` that's deserialized on first use.
+fn static_slice(
+    item: &Type,
+    out: &str,
+    no_std: bool,
+    on_error: OnError,
+) -> Result<proc_macro2::TokenStream, String> {
+    let slice = syn::parse_quote!([#item]);
+    let lit = match literal_type(item) {
+        true => Some(literal(&serde_json::from_str(out).map_err(|e| e.to_string())?, &slice)?),
+        false if no_std => return Err(format!("in `#![no_std]` crates, the items of an iterator block must be made of primitives, arrays, tuples and `&'static str`s; `{}` isn't", item.to_token_stream())),
+        false => None,
+    };
+    let vec = syn::parse_quote!(::std::vec::Vec<#item>);
+    Ok(match lit {
+        Some(lit) => on_error.infallible(quote!({
+            const EDG: &[#item] = &#lit;
+            EDG
+        })),
+        // the error is kept, and handed out as many times as it's asked for
+        None if on_error == OnError::Result => allowed(quote!({
+            static EDG: ::std::sync::LazyLock<::core::result::Result<#vec, ::edg::Error>> =
+                ::std::sync::LazyLock::new(|| ::edg::__private::try_from_json(#out));
+            match &*EDG {
+                ::core::result::Result::Ok(v) => ::core::result::Result::Ok(::std::vec::Vec::as_slice(v)),
+                ::core::result::Result::Err(e) => ::core::result::Result::Err(::core::clone::Clone::clone(e)),
+            }
+        })),
+        None => {
+            let decode = on_error.decode(&vec, "json", quote!(#out));
+            allowed(quote!({
+                static EDG: ::std::sync::LazyLock<#vec> = ::std::sync::LazyLock::new(|| #decode);
+                ::std::vec::Vec::as_slice(&EDG)
+            }))
+        }
+    })
+}
+
+/// Can values of this type be written out by [`literal`]?
+fn literal_type(ty: &Type) -> bool {
+    match ty {
+        Type::Array(a) => literal_type(&a.elem),
+        Type::Tuple(t) => t.elems.iter().all(literal_type),
+        Type::Paren(p) => literal_type(&p.elem),
+        Type::Reference(r) => match &*r.elem {
+            Type::Slice(s) => literal_type(&s.elem),
+            t => t.to_token_stream().to_string() == "str",
+        },
+        ty => number(ty).is_some() || matches!(&*ty.to_token_stream().to_string(), "bool" | "char"),
+    }
+}
+
+/// The name of a numeric primitive type.
+fn number(ty: &Type) -> Option<String> {
+    let Type::Path(p) = ty else { return None };
+    let ident = p.path.get_ident()?.to_string();
+    [
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+        "f32", "f64",
+    ]
+    .contains(&&*ident)
+    .then_some(ident)
+}
+
+/// The number that a string stands in for (see `edg::__private::to_json`):
+/// a float json has no number for, or a 128-bit integer.
+fn stand_in(ty: &Type, s: &str) -> Option<proc_macro2::TokenStream> {
+    let ident = number(ty)?;
+    let name = match s {
+        _ if ident.ends_with("128") => {
+            let lit = match ident.starts_with('u') {
+                true => format!("{}{ident}", s.parse::<u128>().ok()?),
+                false => format!("{}{ident}", s.parse::<i128>().ok()?),
+            }
+            .parse::<proc_macro2::TokenStream>()
+            .ok()?;
+            return Some(match s.starts_with('-') {
+                true => quote!((#lit)),
+                false => lit,
+            });
+        }
+        _ if !ident.starts_with('f') => return None,
+        "NaN" => "NAN",
+        "inf" => "INFINITY",
+        "-inf" => "NEG_INFINITY",
+        _ => return None,
+    };
+    let name = Ident::new(name, proc_macro2::Span::call_site());
+    Some(quote!(#ty::#name))
+}
+
+/// Primitive results are written out as literals, so they can be used in const contexts (like array lengths).
+fn scalar(ty: &Type, out: &str) -> Option<proc_macro2::TokenStream> {
+    if let Some(ident) = number(ty) {
+        if out.starts_with('"') {
+            return stand_in(ty, &serde_json::from_str::<String>(out).ok()?);
+        }
+        let lit = format!("{out}{ident}").parse().ok()?;
+        return Some(match out.starts_with('-') {
+            true => quote!((#lit)),
+            false => lit,
+        });
+    }
+    match &*ty.to_token_stream().to_string() {
+        "bool" | "char" => literal(&serde_json::from_str(out).ok()?, ty).ok(),
+        _ => None,
+    }
+}
+
+/// A value of type `ty` that's usable in consts and statics (which `edg::__private::placeholder` isn't),
+/// for blocks that aren't evaluated: zeroes, `false`, empty strs and slices, and `None`.
+fn zero(ty: &Type) -> Option<proc_macro2::TokenStream> {
+    if let Some(ident) = number(ty) {
+        return format!("0{ident}").parse().ok();
+    }
+    Some(match ty {
+        Type::Array(TypeArray { elem, len, .. }) => {
+            let zero = zero(elem)?;
+            // a const can be repeated even if it isn't `Copy`
+            quote!({
+                const EDG: #elem = #zero;
+                [EDG; #len]
+            })
+        }
+        Type::Tuple(t) => {
+            let zeroes = t.elems.iter().map(zero).collect::<Option<Vec<_>>>()?;
+            quote!((#(#zeroes,)*))
+        }
+        Type::Paren(p) => return zero(&p.elem),
+        Type::Reference(r) => match &*r.elem {
+            Type::Slice(_) => quote!(&[]),
+            t if t.to_token_stream().to_string() == "str" => quote!(""),
+            t => {
+                let zero = zero(t)?;
+                quote!(&#zero)
+            }
+        },
+        Type::Path(p) if p.path.segments.last().is_some_and(|s| s.ident == "Option") => {
+            quote!(::core::option::Option::None)
+        }
+        ty => match &*ty.to_token_stream().to_string() {
+            "bool" => quote!(false),
+            "char" => quote!('\0'),
+            _ => return None,
+        },
+    })
+}
+
+/// Expands `edg::r!` and `edg::json!` inside a block before it is written out.
+/// Leaving them for the script's own compilation would have them wait on the lock we are holding.
+struct Nested;
+
+thread_local! {
+    /// Whether the block being expanded is nested in another.
+    static NESTED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+impl Nested {
+    /// Is this `edg::r!` (`Some(false)`) or `edg::json!` (`Some(true)`)?
+    fn kind(m: &syn::Macro) -> Option<bool> {
+        let path = m
+            .path
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect::<Vec<_>>();
+        match path.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["edg", "r"] => Some(false),
+            ["edg", "json"] => Some(true),
+            _ => None,
+        }
+    }
+
+    /// The outermost nested blocks.
+    fn find(body: &Expr) -> Vec<(Options, Block)> {
+        struct Find(Vec<(Options, Block)>);
+        impl Visit<'_> for Find {
+            fn visit_expr(&mut self, e: &Expr) {
+                if let Expr::Macro(m) = e {
+                    if let Some(json) = Nested::kind(&m.mac) {
+                        self.0.extend(block(m.mac.tokens.clone(), json).ok());
+                        return;
+                    }
+                }
+                syn::visit::visit_expr(self, e);
+            }
+        }
+        let mut find = Find(vec![]);
+        find.visit_expr(body);
+        find.0
+    }
+
+    /// Start compiling every nested block that doesn't itself have nested blocks (whose scripts aren't known yet).
+    fn precompile(body: &Expr) {
+        for (options, block) in Nested::find(body) {
+            match Nested::find(&block.body).is_empty() || options.disabled {
+                true => _ = precompile(&options, &block, None),
+                false => Nested::precompile(&block.body),
+            }
+        }
+    }
+}
+
+impl VisitMut for Nested {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Macro(m) = e {
+            let outer = NESTED.replace(true);
+            let expansion = match Nested::kind(&m.mac) {
+                Some(false) => Some(r(m.mac.tokens.clone())),
+                Some(true) => Some(json(m.mac.tokens.clone())),
+                None => None,
+            };
+            NESTED.set(outer);
+            if let Some(expansion) = expansion {
+                *e = syn::parse_quote!((#expansion));
+                return;
+            }
+        }
+        syn::visit_mut::visit_expr_mut(self, e);
+    }
+}
+
+/// Where a crate's scripts (and everything else edg makes) go: `target/edg/<crate>-<version>`,
+/// so that the crates of a workspace, which share a target directory, stay out of each other's way.
+fn out_dir() -> PathBuf {
+    if let Some(dir) = HOST.with_borrow(|h| h.as_ref().map(|h| h.out_dir.clone())) {
+        _ = std::fs::create_dir_all(&dir);
+        return dir;
+    }
+    let var = |v| std::env::var(v).unwrap_or_default();
+    let name = match var("CARGO_CRATE_NAME") {
+        name if name.is_empty() => var("CARGO_PKG_NAME"),
+        name => name,
+    };
+    let dir = target_dir()
+        .join("edg")
+        .join(format!("{name}-{}", var("CARGO_PKG_VERSION")));
+    _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+/// The target directory that rustc's output (`--out-dir`, like `target/debug/deps`, or rustdoc's `-o`) goes in,
+/// which cargo tags with a `CACHEDIR.TAG`. That's not under the working directory for crates from a registry (or git),
+/// which are built where their source is; without it, `$CARGO_TARGET_DIR` (or `target`) there is used.
+fn target_dir() -> PathBuf {
+    let args = args();
+    let out = args
+        .windows(2)
+        .find_map(|w| matches!(&*w[0], "--out-dir" | "-o").then(|| PathBuf::from(&w[1])))
+        .or_else(|| {
+            args.iter()
+                .find_map(|a| Some(PathBuf::from(a.strip_prefix("--out-dir=")?)))
+        });
+    if let Some(target) = out
+        .iter()
+        .flat_map(|out| out.ancestors())
+        .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+    {
+        return target.to_owned();
+    }
+    let cwd = std::env::current_dir().unwrap_or_else(|_| "/tmp".into());
+    std::env::var_os("CARGO_TARGET_DIR").map_or(cwd.join("target"), |t| cwd.join(t))
+}
+
+/// Is this a `Vec<u8>`?
+fn bytes(ty: &Type) -> bool {
+    let Type::Path(p) = ty else { return false };
+    p.path.segments.last().is_some_and(|last| {
+        last.ident == "Vec" && last.arguments.to_token_stream().to_string() == "< u8 >"
+    })
+}
+
+/// Big `Vec<u8>`s are written to a file and [`include_bytes!`]ed, instead of being embedded as a json string.
+/// The threshold (in bytes) can be set with `EDG_SIDECAR_THRESHOLD`.
+fn sidecar(ty: &Type, out: &str) -> Option<proc_macro2::TokenStream> {
+    if !bytes(ty) {
+        return None;
+    }
+    let threshold = std::env::var("EDG_SIDECAR_THRESHOLD")
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(1 << 20);
+    // the json is at least as long as the data
+    if out.len() <= threshold {
+        return None;
+    }
+    let bytes = serde_json::from_str::<Vec<u8>>(out).ok()?;
+    if bytes.len() <= threshold {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let file = out_dir().join(format!("edg-{}.bin", hasher.finish()));
+    write(&file, &bytes).ok()?;
+    let file = file.to_str()?;
+    let len = bytes.len();
+    Some(quote!({
+        const EDG: &[u8] = ::core::include_bytes!(#file);
+        const _: () = ::core::assert!(EDG.len() == #len, "edg sidecar file was modified");
+        EDG.to_vec()
+    }))
+}
+
+/// Write the result, deflated, to a file, which is [`include_bytes!`]ed and inflated at runtime.
+/// `Vec<u8>`s are stored as is; everything else as json.
+fn compressed(ty: &Type, out: &str, on_error: OnError) -> Option<proc_macro2::TokenStream> {
+    let raw = match bytes(ty) {
+        true => Some(serde_json::from_str::<Vec<u8>>(out).ok()?),
+        false => None,
+    };
+    let data = miniz_oxide::deflate::compress_to_vec(raw.as_deref().unwrap_or(out.as_bytes()), 9);
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    let file = out_dir().join(format!("edg-{}.deflate", hasher.finish()));
+    write(&file, &data).ok()?;
+    let file = file.to_str()?;
+    let len = data.len();
+    let decode = match raw {
+        Some(_) => on_error.infallible(quote!(::edg::__private::inflate(EDG))),
+        None => on_error.decode(ty, "compressed", quote!(EDG)),
+    };
+    Some(quote!({
+        const EDG: &[u8] = ::core::include_bytes!(#file);
+        const _: () = ::core::assert!(EDG.len() == #len, "edg sidecar file was modified");
+        #decode
+    }))
+}
+
+/// Turn a [`type_name`](std::any::type_name) back into a type, if it is made only of primitives and `std` types.
+/// Anything else (private module paths, closures, references) can't reliably be named from the caller.
+fn infer_type(name: &str) -> Result<Type, String> {
+    const PUBLIC: &[(&str, &str)] = &[
+        ("alloc::string::String", "::std::string::String"),
+        ("alloc::vec::Vec", "::std::vec::Vec"),
+        ("alloc::boxed::Box", "::std::boxed::Box"),
+        (
+            "alloc::collections::btree::map::BTreeMap",
+            "::std::collections::BTreeMap",
+        ),
+        (
+            "alloc::collections::btree::set::BTreeSet",
+            "::std::collections::BTreeSet",
+        ),
+        (
+            "alloc::collections::vec_deque::VecDeque",
+            "::std::collections::VecDeque",
+        ),
+        (
+            "std::collections::hash::map::HashMap",
+            "::std::collections::HashMap",
+        ),
+        (
+            "std::collections::hash::set::HashSet",
+            "::std::collections::HashSet",
+        ),
+        ("std::hash::random::RandomState", "::std::hash::RandomState"),
+        ("core::option::Option", "::core::option::Option"),
+        ("core::result::Result", "::core::result::Result"),
+    ];
+    struct Nameable(bool);
+    impl<'a> Visit<'a> for Nameable {
+        fn visit_type(&mut self, t: &'a Type) {
+            match t {
+                Type::Path(p) if p.qself.is_none() => {
+                    let p = &p.path;
+                    self.0 &= match p.segments.first() {
+                        Some(s) if p.leading_colon.is_some() => {
+                            s.ident == "std" || s.ident == "core"
+                        }
+                        Some(s) => {
+                            p.segments.len() == 1
+                                && s.arguments.is_empty()
+                                && matches!(
+                                    &*s.ident.to_string(),
+                                    "u8" | "u16"
+                                        | "u32"
+                                        | "u64"
+                                        | "u128"
+                                        | "usize"
+                                        | "i8"
+                                        | "i16"
+                                        | "i32"
+                                        | "i64"
+                                        | "i128"
+                                        | "isize"
+                                        | "f32"
+                                        | "f64"
+                                        | "bool"
+                                        | "char"
+                                )
+                        }
+                        None => false,
+                    };
+                }
+                Type::Tuple(_) | Type::Array(_) | Type::Paren(_) | Type::Group(_) => {}
+                _ => self.0 = false,
+            }
+            syn::visit::visit_type(self, t);
+        }
+    }
+
+    let mut public = name.to_owned();
+    for (from, to) in PUBLIC {
+        public = public.replace(from, to);
+    }
+    match syn::parse_str::<Type>(&public) {
+        Ok(t) if { let mut n = Nameable(true); n.visit_type(&t); n.0 } => Ok(t),
+        _ => Err(format!(
+            "cannot name the inferred return type `{name}` from here; annotate the closure (`|| -> T {{ .. }}`)"
+        )),
+    }
+}
+
+/// Writes tokens out where they are in the caller's file: each on its line, and (if there's room) at its column.
+/// Positions in the script are then positions in that file, which is how panics are traced back to the block.
+struct Layout {
+    out: String,
+    file: String,
+    line: usize,
+    column: usize,
+    /// where each token is, when their spans don't say
+    at: Option<std::vec::IntoIter<(usize, usize)>>,
+}
+
+/// Where the tokens of a block that was read from the source (see [`speculate`]) are, in the order they're written out.
+pub(crate) struct Place {
+    file: String,
+    /// lines and columns
+    at: Vec<(usize, usize)>,
+}
+
+/// How a group is written out.
+fn delimiters(d: proc_macro2::Delimiter) -> (&'static str, &'static str) {
+    use proc_macro2::Delimiter;
+    match d {
+        Delimiter::Parenthesis => ("(", ")"),
+        Delimiter::Brace => ("{", "}"),
+        Delimiter::Bracket => ("[", "]"),
+        Delimiter::None => ("", ""),
+    }
+}
+
+impl Layout {
+    fn new(header: String, place: Option<Place>) -> Self {
+        let (file, at) = match place {
+            Some(Place { file, at }) => (file, Some(at.into_iter())),
+            None => (call_site_file(), None),
+        };
+        Self {
+            column: header.chars().count() + 1,
+            out: header,
+            file,
+            line: 1,
+            at,
+        }
+    }
+
+    fn push(&mut self, text: &str, span: Option<proc_macro2::Span>, space: bool) {
+        let at = self.at.as_mut().map(|at| at.next().unwrap_or_default());
+        let (file, line, column) = match (span, at) {
+            (Some(_), Some((line, column))) => (self.file.clone(), line, column),
+            (Some(span), None) if proc_macro::is_available() => {
+                let span = span.unwrap();
+                (span.file(), span.line(), span.column())
+            }
+            // under `edg-test`, tokens have no place in the file
+            _ => (String::new(), 0, 0),
+        };
+        // tokens from elsewhere (like other macros) go wherever they fit
+        if file == self.file && line > self.line {
+            self.out.extend(std::iter::repeat_n('\n', line - self.line));
+            self.line = line;
+            self.column = 1;
+        }
+        if file == self.file && line == self.line && column >= self.column {
+            self.out
+                .extend(std::iter::repeat_n(' ', column - self.column));
+            self.column = column;
+        } else if space {
+            self.out.push(' ');
+            self.column += 1;
+        }
+        self.out.push_str(text);
+        match text.rsplit_once('\n') {
+            Some((before, after)) => {
+                self.line += before.matches('\n').count() + 1;
+                self.column = after.chars().count() + 1;
+            }
+            None => self.column += text.chars().count(),
+        }
+    }
+
+    fn tokens(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::{Spacing, TokenTree};
+        // no space after a joint punct (`::`, `->`, `'a`)
+        let mut joint = true;
+        for token in tokens {
+            let space = !joint;
+            joint = false;
+            match token {
+                TokenTree::Group(g) => {
+                    let (open, close) = delimiters(g.delimiter());
+                    self.push(open, Some(g.span_open()), space);
+                    self.tokens(g.stream());
+                    // syn makes most groups anew, with one span, which starts where they do
+                    self.push(close, None, true);
+                }
+                TokenTree::Punct(p) => {
+                    self.push(&p.as_char().to_string(), Some(p.span()), space);
+                    joint = p.spacing() == Spacing::Joint;
+                }
+                token => self.push(&token.to_string(), Some(token.span()), space),
+            }
+        }
+    }
+}
+
+/// The script that evaluates `body`, and its hash.
+/// When `infer`ring, the first line the script prints is the name of the type.
+fn script(
+    options: &Options,
+    ty: Option<&Type>,
+    infer: bool,
+    body: &Expr,
+    place: Option<Place>,
+) -> (String, u64) {
+    let ty = ty.map_or_else(String::new, |ty| format!(": {}", ty.to_token_stream()));
+    let mut code = Layout::new(
+        format!(
+        // nested blocks are expanded in parens (or braces), which may well be unnecessary
+        "#![allow(unused_parens, unused_braces)] fn main() {{ ::edg::__private::hook(); let res{ty} = "
+    ),
+        place,
+    );
+    code.tokens(body.to_token_stream());
+    let code = code.out;
+    let name = if infer {
+        r#"println!("{}", std::any::type_name_of_val(&res));"#
+    } else {
+        ""
+    };
+    let ser = if options.construct {
+        "let mut ser = String::new(); ::edg::ConstructTokens::construct(&res, &mut ser);"
+    } else {
+        "let ser = ::edg::__private::to_json(&res);"
+    };
+    let script = format!(
+        r#"{code}
+; // surely nobody will main()
+                    {name}
+                    {ser}
+                    ::edg::__private::emit(&ser);
+                }}"#
+    );
+    let mut hasher = DefaultHasher::new();
+    script.hash(&mut hasher);
+    test_cfg(options).hash(&mut hasher);
+    options.toolchain.hash(&mut hasher);
+    (script, hasher.finish())
+}
+
+/// `--cfg test`, if the script should have it.
+fn test_cfg(options: &Options) -> &'static [&'static str] {
+    match options.test && args().iter().any(|a| a == "--test") {
+        true => &["--cfg", "test"],
+        false => &[],
+    }
+}
+
+/// `rustc` (or `$RUSTC`), behind the wrappers cargo would put it behind (like sccache).
+/// With a toolchain, `rustup run` puts that toolchain's `rustc` first in the `PATH`, so it's run there.
+fn rustc(toolchain: Option<&str>) -> Command {
+    let var = |v| std::env::var_os(v).filter(|w| !w.is_empty());
+    let mut program = vec![];
+    program.extend(var("RUSTC_WRAPPER"));
+    // cargo only uses this one for workspace members, which are most likely the packages it was asked to build
+    if std::env::var_os("CARGO_PRIMARY_PACKAGE").is_some() {
+        program.extend(var("RUSTC_WORKSPACE_WRAPPER"));
+    }
+    match toolchain {
+        Some(toolchain) => {
+            program.push("rustc".into());
+            let mut rustup = Command::new("rustup");
+            rustup.args(["run", toolchain]).args(program);
+            rustup
+        }
+        None => {
+            program.push(var("RUSTC").unwrap_or("rustc".into()));
+            let mut rustc = Command::new(&program[0]);
+            rustc.args(&program[1..]);
+            rustc
+        }
+    }
+}
+
+/// The command that compiles the script at `file`, and where it puts the result.
+/// Miri interprets the script instead, so there's nothing to compile.
+fn compiler(options: &Options, hash: u64, file: &Path) -> Option<(Command, PathBuf)> {
+    let args = args();
+    let out_dir = file.parent()?;
+    let mut rustc = rustc(options.toolchain.as_deref());
+    rustc.env("EDG_NESTED", "1");
+    let out = match options.backend {
+        Backend::Miri => return None,
+        Backend::Wasm => {
+            rustc.args(foreign_args(
+                &args,
+                "EDG_WASM_DEPS",
+                options.toolchain.is_none(),
+            ));
+            rustc.args(["--target", "wasm32-wasip1"]);
+            out_dir.join(format!("edg-{hash}.wasm"))
+        }
+        // the host's dependencies were built by another compiler, which this one can't use
+        Backend::Native if options.toolchain.is_some() => {
+            rustc.args(foreign_args(&args, "EDG_TOOLCHAIN_DEPS", false));
+            out_dir.join(format!("edg_{hash}{}", std::env::consts::EXE_SUFFIX))
+        }
+        // the host's dependencies were built for another target (or without `std`), which the script can't run on
+        Backend::Native if std::env::var_os("EDG_HOST_DEPS").is_some() => {
+            rustc.args(foreign_args(&args, "EDG_HOST_DEPS", true));
+            out_dir.join(format!("edg_{hash}{}", std::env::consts::EXE_SUFFIX))
+        }
+        Backend::Native => {
+            rustc.args(filter_rustc_args(&args));
+            rustc.args(externs::externs(&args));
+            // named after the hash, so that builds running at the same time can't clobber each other's binary
+            out_dir.join(format!("edg_{hash}{}", std::env::consts::EXE_SUFFIX))
+        }
+    };
+    rustc.args(test_cfg(options));
+    // for backtraces; these win over whatever the host was given
+    rustc.args(["-C", "debuginfo=line-tables-only", "-C", "strip=none"]);
+    rustc.args(["--crate-name", "edg_bin"]);
+    rustc.args(["--crate-type", "bin"]);
+    rustc.arg("-o").arg(&out);
+    rustc.arg(file);
+    Some((rustc, out))
+}
+
+/// How `rustc` is run, one argument per line; kept next to a binary (as `.args`) to tell whether it can be reused.
+fn command_line(rustc: &Command) -> String {
+    std::iter::once(rustc.get_program())
+        .chain(rustc.get_args())
+        .map(|a| a.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Is `out` what `rustc` would compile now: was it compiled by the same command, after what it links to was built?
+fn reusable(rustc: &Command, out: &Path) -> bool {
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    let Some(built) = modified(out) else {
+        return false;
+    };
+    let args = rustc
+        .get_args()
+        .map(|a| a.to_string_lossy())
+        .collect::<Vec<_>>();
+    std::fs::read_to_string(out.with_extension("args")).is_ok_and(|l| l == command_line(rustc))
+        && args
+            .windows(2)
+            .filter(|w| w[0] == "--extern")
+            .filter_map(|w| Some(w[1].split_once('=')?.1))
+            .all(|path| modified(Path::new(path)).is_some_and(|m| m <= built))
+}
+
+/// Start compiling a block in the background, so it's ready (or closer to it) by the time it's expanded.
+/// Returns the script's hash, and the files it was written to and is being compiled to.
+fn precompile(
+    options: &Options,
+    Block { ty, host, body, .. }: &Block,
+    place: Option<Place>,
+) -> Option<(u64, [PathBuf; 2])> {
+    if cfg!(feature = "runtime-fallback")
+        || metadata_only()
+        || options.disabled
+        || std::env::var_os("EDG_NESTED").is_some()
+    {
+        return None;
+    }
+    let infer = ty.is_none() && host.is_none() && !options.construct;
+    let mut body = body.clone();
+    if Captures::apply(&mut body, false).is_err() || Modules::apply(&mut body).is_err() {
+        return None;
+    }
+    let (script, hash) = script(options, ty.as_ref(), infer, &body, place);
+    let file = out_dir().join(format!("edg-{hash}.rs"));
+    let (rustc, out) = compiler(options, hash, &file)?;
+    if reusable(&rustc, &out) {
+        return None;
+    }
+    // not while someone else is evaluating it (it's theirs to remove); once it's submitted, it's
+    // `run`'s to check that it was still there
+    let Ok(Some(_lock)) = try_lock(&out_dir(), hash) else {
+        return None;
+    };
+    // another build's pool may be compiling it right now, so it's only (re)written if it isn't this script
+    // (it could be left over from a killed build)
+    if std::fs::read(&file).is_ok_and(|f| f == script.as_bytes())
+        || write(&file, script.as_bytes()).is_ok()
+    {
+        pool::submit(hash, rustc);
+        return Some((hash, [file, out]));
+    }
+    None
+}
+
+/// What a block printed.
+pub(crate) struct Evaluated {
+    /// the serialized result
+    /// (when `infer`ring, the first line is the name of the type)
+    pub(crate) out: String,
+    /// warnings from compiling the script, and whatever it wrote to stderr
+    notes: Vec<String>,
+    /// the files of the block's modules
+    files: Vec<PathBuf>,
+}
+
+impl Evaluated {
+    /// Items to go with the result: ones that make rustc rebuild when the block's inputs change, and its warnings.
+    fn items(&self, options: &Options) -> proc_macro2::TokenStream {
+        let track = options.track(&self.files);
+        let warnings = warnings(&self.notes);
+        quote!(#track #warnings)
+    }
+}
+
+/// Compile and run `body`, returning its serialized output.
+pub(crate) fn run(
+    options: &Options,
+    ty: Option<&Type>,
+    infer: bool,
+    body: &Expr,
+) -> Result<Evaluated, String> {
+    if std::env::var_os("EDG_NESTED").is_some() {
+        return Err("edg macros inside a comptime block are only supported as `edg::r!`/`edg::json!` expressions, which are evaluated before the block; move this one out".into());
+    }
+    speculate::start();
+    let mut body = body.clone();
+    Captures::apply(&mut body, false)?;
+    let files = Modules::apply(&mut body)?;
+    // nested blocks don't depend on each other, so they can all be compiled at once
+    Nested::precompile(&body);
+    Nested.visit_expr_mut(&mut body);
+
+    let (script, hash) = script(options, ty, infer, &body, None);
+    let out_dir = out_dir();
+    let mut lock = lock(&out_dir, hash)?;
+    macro_rules! err {
+        ($fstr:literal$(,)? $( $arg:expr ),*) => {
+            return Err(format!($fstr, $($arg),*))
+        };
+    }
+
+    let stdin = match &options.stdin {
+        Some(path) => match std::fs::read(path) {
+            Ok(data) => Some(data),
+            Err(e) => err!("could not read {}: {e}", path.display()),
+        },
+        None => None,
+    };
+
+    // the last output of every block is kept, for rustdoc and `cargo check`
+    let record = match &stdin {
+        Some(data) => {
+            let mut hasher = DefaultHasher::new();
+            data.hash(&mut hasher);
+            out_dir.join(format!("edg-{hash}-{}.out", hasher.finish()))
+        }
+        None => out_dir.join(format!("edg-{hash}.out")),
+    };
+    if metadata_only() {
+        let out = std::fs::read_to_string(record)
+            .ok()
+            .and_then(|record| checked(&record).map(str::to_owned))
+            .ok_or(NOT_EVALUATED)?;
+        stats::record(
+            out_dir,
+            stats::Block {
+                at: at(),
+                hash,
+                cached: true,
+                precompiled: false,
+                compile: Duration::ZERO,
+                run: Duration::ZERO,
+                bytes: out.len(),
+            },
+        );
+        return Ok(Evaluated {
+            out,
+            notes: vec![],
+            files,
+        });
+    }
+
+    let file = out_dir.join(format!("edg-{hash}.rs"));
+    if let Err(e) = write(&file, script.as_bytes()) {
+        err!("could not write {}: {e}", file.display());
+    }
+    lock.temporaries.push(file.clone());
+
+    let progress = Progress::start();
+    let mut notes = vec![];
+    // was the compile started ahead of time, and how long it was waited on
+    let mut compiled = (false, Duration::ZERO);
+    let mut reused = false;
+    let comptime_output = match compiler(options, hash, &file) {
+        None => match miri(test_cfg(options), &file, stdin.as_deref()) {
+            Ok(o) if o.status.success() => {
+                notes.extend(stderr_notes(&o.stderr));
+                o.stdout
+            }
+            Ok(o) => err!(
+                "{}",
+                failure(&String::from_utf8_lossy(&o.stderr), &file).unwrap_or_else(|e| format!(
+                    "could not run comptime expr under miri:\n\n{e}\n"
+                ))
+            ),
+            Err(e) => err!("could not invoke miri: {e}"),
+        },
+        Some((mut rustc, out)) => {
+            let precompiled = pool::submitted(hash);
+            // a binary from an earlier build is run again, if its output was recorded (so it ran)
+            reused = !precompiled && record.exists() && reusable(&rustc, &out);
+            if !reused {
+                let start = Instant::now();
+                let deadline = start + externs::PATIENCE;
+                // the script was only locked while it was written out, and could have been removed
+                // (by another build of the crate) before it was compiled
+                let mut unlocked = precompiled;
+                let (compile_output, line) = loop {
+                    let line = command_line(&rustc);
+                    let o = match pool::compile(hash, rustc) {
+                        Ok(o) => o,
+                        Err(e) => err!("could not invoke rustc: {e}"),
+                    };
+                    let again =
+                        std::mem::take(&mut unlocked) && !(o.status.success() && out.exists());
+                    // a crate's rlib may still be on its way, in a pipelined build (see `externs`)
+                    if !again
+                        && (o.status.success()
+                            || !externs::unlinked(&o.stderr)
+                            || Instant::now() >= deadline)
+                    {
+                        break (o, line);
+                    }
+                    if !again {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                    match compiler(options, hash, &file) {
+                        Some((again, _)) => rustc = again,
+                        None => break (o, line),
+                    }
+                };
+                compiled = (precompiled, start.elapsed());
+                if !compile_output.status.success() {
+                    let stderr = String::from_utf8_lossy(&compile_output.stderr);
+                    if let Some(ty) = unserializable(&stderr).filter(|_| !options.construct) {
+                        err!("{UNSERIALIZABLE}, which `{ty}` doesn't; add `#[derive(serde::Serialize, serde::Deserialize)]` to it");
+                    }
+                    let log = out_dir.join(format!("edg-{hash}.log"));
+                    _ = std::fs::write(&log, &*stderr);
+                    err!(
+                        "could not compile comptime expr:\n\n{}\n\n(the full output is in {})",
+                        compile_errors(&stderr, &file),
+                        log.display()
+                    );
+                }
+                _ = write(&out.with_extension("args"), line.as_bytes());
+                notes.extend(compile_notes(&compile_output.stderr));
+            }
+
+            let output = match options.backend {
+                Backend::Wasm => wasm(&out, stdin.as_deref().unwrap_or_default()),
+                _ => native(&out, stdin.as_deref()),
+            };
+            match output {
+                Ok((stdout, stderr)) => {
+                    notes.extend(stderr_notes(&stderr));
+                    stdout
+                }
+                Err(e) => err!(
+                    "{}",
+                    failure(&e, &file)
+                        .unwrap_or_else(|e| format!("could not run comptime expr:\n\n{e}\n"))
+                ),
+            }
+        }
+    };
+
+    let mut comptime_output = comptime_output;
+    // big outputs come deflated (see `edg::__private::emit`)
+    let start = match infer {
+        true => comptime_output
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(0, |i| i + 1),
+        false => 0,
+    };
+    if comptime_output.get(start) == Some(&0) {
+        match miniz_oxide::inflate::decompress_to_vec(&comptime_output[start + 1..]) {
+            Ok(data) => {
+                comptime_output.truncate(start);
+                comptime_output.extend(data);
+            }
+            Err(e) => err!("could not inflate comptime expr output: {e}"),
+        }
+    }
+
+    let comptime_expr = if let Ok(output) = String::from_utf8(comptime_output) {
+        output
+    } else {
+        err!("comptime expr output was not utf8")
+    };
+
+    _ = write(
+        &record,
+        format!("{}\n{comptime_expr}", checksum(&comptime_expr)).as_bytes(),
+    );
+
+    drop(lock);
+    notes.extend(progress.slow());
+    stats::record(
+        out_dir,
+        stats::Block {
+            at: progress.at.clone(),
+            hash,
+            cached: reused,
+            precompiled: compiled.0,
+            compile: compiled.1,
+            run: progress.start.elapsed().saturating_sub(compiled.1),
+            bytes: comptime_expr.len(),
+        },
+    );
+    Ok(Evaluated {
+        out: comptime_expr,
+        notes,
+        files,
+    })
+}
+
+/// Write a file all at once (by way of a temporary file), so that a killed build can't leave half of one behind.
+fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| _ = std::fs::remove_file(&tmp))
+}
+
+fn checksum(data: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The output in a record (`checksum\noutput`), if it's intact.
+fn checked(record: &str) -> Option<&str> {
+    let (sum, out) = record.split_once('\n')?;
+    (sum.parse() == Ok(checksum(out))).then_some(out)
+}
+
+/// Starts the error for a result that can't be serialized, which is reported at the block's return type.
+const UNSERIALIZABLE: &str =
+    "edg: the result of a block must implement `serde::Serialize` and `serde::Deserialize`";
+
+/// The type the script failed to compile for not implementing `Serialize`, if that's why it did.
+fn unserializable(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        let msg = match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(d) => d["message"].as_str()?.to_owned(),
+            Err(_) => line.split_once("]: ")?.1.to_owned(),
+        };
+        // "the trait bound `T: serde::Serialize` is not satisfied", or "the trait `Serialize` is not implemented for `T`"
+        match msg.strip_prefix("the trait bound `") {
+            Some(bound) => {
+                let (ty, bound) = bound
+                    .split_once("` is not satisfied")?
+                    .0
+                    .rsplit_once(": ")?;
+                bound.ends_with("Serialize").then(|| ty.to_owned())
+            }
+            None => {
+                let ty = msg
+                    .strip_prefix("the trait `")?
+                    .split_once("` is not implemented for `")?;
+                ty.0.ends_with("Serialize")
+                    .then(|| ty.1.trim_end_matches('`').to_owned())
+            }
+        }
+    })
+}
+
+/// Make a panic (reported by `edg::__private::hook`) into a short message, pointing into the caller's file,
+/// unless `edg::on_failure` has one. Anything else is handed back as is.
+fn failure(stderr: &str, script: &Path) -> Result<String, String> {
+    let mut panic = None;
+    let mut printed = String::new();
+    for line in stderr.lines() {
+        match line.strip_prefix("edg::panic ") {
+            Some(p) if panic.is_none() => panic = serde_json::from_str::<serde_json::Value>(p).ok(),
+            _ => printed.extend([line, "\n"]),
+        }
+    }
+    let Some(panic) = panic else {
+        return Err(stderr.to_owned());
+    };
+    // `edg::on_failure` put it in the caller's terms
+    if let Some(explained) = panic["explained"].as_str() {
+        return Ok(explained.to_owned());
+    }
+    let file = panic["file"].as_str().unwrap_or_default();
+    // the script is laid out like the caller's file
+    let file = match Path::new(file).file_name() == script.file_name() {
+        true => call_site_file(),
+        false => file.to_owned(),
+    };
+    let mut msg = format!(
+        "comptime expression {} at {file}:{}:{}: {}",
+        match panic["err"].as_bool() {
+            Some(true) => "failed",
+            _ => "panicked",
+        },
+        panic["line"],
+        panic["column"],
+        panic["message"].as_str().unwrap_or_default()
+    );
+    let frames = frames(panic["backtrace"].as_str().unwrap_or_default(), script);
+    if frames.len() > 1 {
+        msg += &format!("\n\nbacktrace:\n{}", frames.join("\n"));
+    }
+    if !printed.trim().is_empty() {
+        msg += &format!("\n\nit printed:\n{printed}");
+    }
+    Ok(msg)
+}
+
+/// The frames of a [`Backtrace`](std::backtrace::Backtrace) that are the script's (or its dependencies'),
+/// with locations in the script pointing into the caller's file instead.
+fn frames(backtrace: &str, script: &Path) -> Vec<String> {
+    let mut frames: Vec<(&str, Option<&str>)> = vec![];
+    for line in backtrace.lines().map(str::trim) {
+        match (line.strip_prefix("at "), line.split_once(": ")) {
+            (Some(at), _) => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.1.get_or_insert(at);
+                }
+            }
+            (None, Some((n, name))) if n.parse::<usize>().is_ok() => frames.push((name, None)),
+            _ => {}
+        }
+    }
+    // std marks where the panic machinery ends, and where `main` was called from
+    let start = frames
+        .iter()
+        .position(|(name, _)| name.contains("__rust_end_short_backtrace"))
+        .map_or(0, |i| i + 1);
+    let end = frames
+        .iter()
+        .position(|(name, _)| name.contains("__rust_begin_short_backtrace"))
+        .unwrap_or(frames.len());
+    let file = call_site_file();
+    frames
+        .get(start..end)
+        .unwrap_or_default()
+        .iter()
+        .filter(|(name, at)| match at {
+            Some(at) => !at.starts_with("/rustc/"),
+            None => !["std::", "core::", "alloc::", "__rustc::"]
+                .iter()
+                .any(|p| name.starts_with(p)),
+        })
+        .map(|&(name, at)| {
+            let at = at.map(|at| {
+                let (path, position) = at.split_once(':').unwrap_or((at, ""));
+                match Path::new(path).file_name() == script.file_name() {
+                    true => format!("{file}:{position}"),
+                    false => at.to_owned(),
+                }
+            });
+            match at {
+                Some(at) => format!("  {name}\n      at {at}"),
+                None => format!("  {name}"),
+            }
+        })
+        .collect()
+}
+
+/// The first few errors rustc gave for the script, pointing into the caller's file.
+fn compile_errors(stderr: &str, script: &Path) -> String {
+    const MAX: usize = 3;
+    let mut errors = vec![];
+    let mut human = None::<String>;
+    for line in stderr.lines() {
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(d)
+                if d["level"] == "error"
+                    && !d["message"]
+                        .as_str()
+                        .is_some_and(|m| m.starts_with("aborting due to")) =>
+            {
+                errors.extend(d["rendered"].as_str().map(str::to_owned));
+            }
+            Ok(_) => {}
+            Err(_) if line.starts_with("error") && !line.starts_with("error: aborting") => {
+                errors.extend(human.replace(format!("{line}\n")));
+            }
+            // an error goes on until the next blank line
+            Err(_) if line.trim().is_empty() => errors.extend(human.take()),
+            Err(_) => {
+                if let Some(e) = &mut human {
+                    e.extend([line, "\n"]);
+                }
+            }
+        }
+    }
+    errors.extend(human);
+    let more = errors.len().saturating_sub(MAX);
+    let mut out = errors
+        .iter()
+        .take(MAX)
+        .map(|e| plain(e).trim_end().to_owned())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if more > 0 {
+        out += &format!("\n\n... and {more} more");
+    }
+    // the script is laid out like the caller's file
+    match script.to_str() {
+        Some(path) => out.replace(path, &call_site_file()),
+        None => out,
+    }
+}
+
+/// Without ansi escapes (which cargo asks rustc to color json diagnostics with).
+fn plain(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => _ = chars.by_ref().find(|c| c.is_ascii_alphabetic()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The warnings rustc gave for the script.
+/// Under cargo, those come as json (the host's `--error-format` is passed on).
+fn compile_notes(stderr: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter_map(
+            |line| match serde_json::from_str::<serde_json::Value>(line) {
+                // the "n warnings emitted" summary has no spans
+                Ok(d)
+                    if d["level"] == "warning"
+                        && d["spans"].as_array().is_some_and(|s| !s.is_empty()) =>
+                {
+                    Some(format!("edg: warning in block: {}", d["message"].as_str()?))
+                }
+                Ok(_) => None,
+                Err(_) => line
+                    .strip_prefix("warning: ")
+                    .filter(|l| !l.ends_with(" emitted"))
+                    .map(|l| format!("edg: warning in block: {l}")),
+            },
+        )
+        .collect()
+}
+
+/// What the script wrote to stderr, a line at a time.
+fn stderr_notes(stderr: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| format!("edg: block printed: {l}"))
+        .collect()
+}
+
+/// Notes (on stderr, which cargo shows as it comes) while a block is slow to evaluate,
+/// so that it doesn't look like a hung compiler, and how long it took in the end.
+struct Progress {
+    _done: std::sync::mpsc::Sender<()>,
+    start: Instant,
+    at: String,
+}
+
+impl Progress {
+    const EVERY: Duration = Duration::from_secs(5);
+
+    fn start() -> Self {
+        let at = at();
+        let start = Instant::now();
+        let (done, rx) = std::sync::mpsc::channel::<()>();
+        let at_ = at.clone();
+        std::thread::spawn(move || {
+            while rx
+                .recv_timeout(Self::EVERY)
+                .is_err_and(|e| e == RecvTimeoutError::Timeout)
+            {
+                eprintln!(
+                    "note: edg: still evaluating block at {at_}, {}s elapsed…",
+                    start.elapsed().as_secs()
+                );
+            }
+        });
+        Self {
+            _done: done,
+            start,
+            at,
+        }
+    }
+}
+
+impl Progress {
+    /// A note if the block took longer than `EDG_WARN_AFTER` (like `10s`, `500ms` or `2m`).
+    fn slow(&self) -> Option<String> {
+        let limit = std::env::var("EDG_WARN_AFTER").ok()?;
+        let took = self.start.elapsed();
+        (took > duration(&limit)?).then(|| {
+            format!(
+                "edg: block at {} took {:.1}s (over EDG_WARN_AFTER={limit})",
+                self.at,
+                took.as_secs_f32()
+            )
+        })
+    }
+}
+
+/// `10s`, `500ms`, `2m`, or a number of seconds.
+fn duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (n, unit) = s.split_at(s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len()));
+    let n = n.trim().parse::<f64>().ok()?;
+    let secs = match unit {
+        "" | "s" => n,
+        "ms" => n / 1000.,
+        "m" => n * 60.,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        let took = self.start.elapsed();
+        if took >= Self::EVERY {
+            eprintln!(
+                "note: edg: block at {} took {:.1}s",
+                self.at,
+                took.as_secs_f32()
+            );
+        }
+    }
+}
+
+/// Doc builds expand macros inside rustdoc, which can't compile (or link against) anything.
+/// Doctests are fine, as those are compiled by a separate rustc.
+fn rustdoc() -> bool {
+    std::env::args_os()
+        .next()
+        .and_then(|a| Some(Path::new(&a).file_stem()?.to_str()? == "rustdoc"))
+        .unwrap_or(false)
+}
+
+/// Is only the crate's metadata being built, by rustdoc or (as with `cargo check` and clippy) by a rustc whose
+/// dependencies have no rlibs to link scripts against? Blocks aren't evaluated then; they're what they were in the
+/// last normal build, or placeholders.
+fn metadata_only() -> bool {
+    rustdoc() || host::metadata_only()
+}
+
+const NOT_EVALUATED: &str =
+    "edg blocks are not evaluated when only metadata is built (by rustdoc or `cargo check`)";
+
+/// Run the compiled script, returning its stdout and stderr.
+fn native(bin: &Path, stdin: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let output = output(&mut Command::new(bin), stdin)
+        .map_err(|e| format!("could not invoke the comptime binary: {e}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+    Ok((output.stdout, output.stderr))
+}
+
+/// Run `cmd`, piping in `stdin`, if any.
+fn output(cmd: &mut Command, stdin: Option<&[u8]>) -> std::io::Result<Output> {
+    let Some(data) = stdin else {
+        return cmd.output();
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut pipe = child.stdin.take().unwrap();
+    std::thread::scope(|s| {
+        // from another thread, as the script may well write before it has read everything
+        s.spawn(move || _ = std::io::Write::write_all(&mut pipe, data));
+        child.wait_with_output()
+    })
+}
+
+/// Interpret the script with the miri driver, which takes the same arguments as rustc.
+fn miri(cfg: &[&str], file: &Path, stdin: Option<&[u8]>) -> std::io::Result<Output> {
+    let args = args();
+    let toolchain = std::env::var("EDG_MIRI_TOOLCHAIN").unwrap_or_else(|_| "nightly".into());
+    let setup = Command::new("cargo")
+        .arg(format!("+{toolchain}"))
+        .args(["miri", "setup", "--print-sysroot"])
+        .output()?;
+    if !setup.status.success() {
+        return Err(std::io::Error::other(
+            String::from_utf8_lossy(&setup.stderr).into_owned(),
+        ));
+    }
+    let sysroot = String::from_utf8_lossy(&setup.stdout).trim().to_owned();
+    let mut miri = Command::new("rustup");
+    miri.env("EDG_NESTED", "1")
+        .args(["run", &toolchain, "miri"])
+        .args(["--sysroot", &sysroot])
+        .args(filter_rustc_args(&args))
+        .args(cfg)
+        .args(["--crate-name", "edg_bin"])
+        .args(["--crate-type", "bin"])
+        .args(externs::externs(&args))
+        .arg(file);
+    output(&mut miri, stdin)
+}
+
+/// Arguments for compiling the script with dependencies other than the host's
+/// (for wasm, with another toolchain, or for the machine doing the build), which are looked up (by name) in the directory `deps` names,
+/// like a `target/wasm32-wasip1/*/deps`.
+/// The proc macros those were built with are in the host's directory of that build (`target/*/deps`).
+/// Proc macros run on the host, so if the compiler is the host's (`host_macros`),
+/// the host's own proc macros are passed on as they are.
+fn foreign_args(args: &[String], deps: &str, host_macros: bool) -> Vec<String> {
+    let mut ret = vec![];
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match &**arg {
+            "--edition" | "--cfg" => {
+                ret.push(arg.clone());
+                ret.extend(it.next().cloned());
+            }
+            "--extern" => match it.next() {
+                Some(e) if host_macros && e.ends_with(std::env::consts::DLL_SUFFIX) => {
+                    ret.extend(["--extern".into(), e.clone()]);
+                }
+                Some(e) => {
+                    let name = e.split('=').next().unwrap_or(e);
+                    ret.extend(["--extern".into(), name.into()]);
+                }
+                None => {}
+            },
+            a if a.starts_with("--edition=") => ret.push(arg.clone()),
+            _ => {}
+        }
+    }
+    if let Some(deps) = std::env::var_os(deps) {
+        let deps = PathBuf::from(deps);
+        // target/<triple>/<profile>/deps -> target/<profile>/deps
+        let macros = deps.parent().and_then(|profile| {
+            let target = profile.parent()?.parent()?;
+            Some(target.join(profile.file_name()?).join("deps"))
+        });
+        for dir in [deps].into_iter().chain(macros) {
+            if dir.is_dir() {
+                ret.push("-L".into());
+                ret.push(dir.to_string_lossy().into_owned());
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(feature = "wasm")]
+/// Run a `wasm32-wasip1` command module, returning its stdout and stderr.
+fn wasm(module: &Path, stdin: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    use wasmtime::{Engine, Linker, Module, Store};
+    use wasmtime_wasi::{
+        p1::{self, WasiP1Ctx},
+        p2::pipe::{MemoryInputPipe, MemoryOutputPipe},
+        I32Exit, WasiCtxBuilder,
+    };
+
+    let engine = Engine::default();
+    let module = Module::from_file(&engine, module).map_err(|e| e.to_string())?;
+    let mut linker: Linker<WasiP1Ctx> = Linker::new(&engine);
+    p1::add_to_linker_sync(&mut linker, |t| t).map_err(|e| e.to_string())?;
+    let stdout = MemoryOutputPipe::new(usize::MAX);
+    let stderr = MemoryOutputPipe::new(usize::MAX);
+    let wasi = WasiCtxBuilder::new()
+        .stdin(MemoryInputPipe::new(stdin.to_vec()))
+        .stdout(stdout.clone())
+        .stderr(stderr.clone())
+        .build_p1();
+    let mut store = Store::new(&engine, wasi);
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|e| e.to_string())?;
+    let start = instance
+        .get_typed_func::<(), ()>(&mut store, "_start")
+        .map_err(|e| e.to_string())?;
+    let status = match start.call(&mut store, ()) {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<I32Exit>() {
+            Some(I32Exit(code)) => *code,
+            None => {
+                return Err(format!(
+                    "{e:?}\n{}",
+                    String::from_utf8_lossy(&stderr.contents())
+                ))
+            }
+        },
+    };
+    if status != 0 {
+        return Err(String::from_utf8_lossy(&stderr.contents()).into_owned());
+    }
+    Ok((stdout.contents().to_vec(), stderr.contents().to_vec()))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn wasm(_: &Path, _: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    Err("the wasm backend needs edg's `wasm` feature".into())
+}
+
+fn filter_rustc_args(args: &[String]) -> Vec<&str> {
+    let mut rustc_args = Vec::with_capacity(args.len());
+    let mut skip = true;
+    for arg in args {
+        if &**arg == "-" {
+            continue;
+        }
+        if skip {
+            skip = false;
+            continue;
+        }
+        if arg == "--crate-type"
+            || arg == "--crate-name"
+            || arg == "--extern"
+            || arg == "--out-dir"
+            || arg == "-o"
+        {
+            skip = true;
+        } else if arg.starts_with("extra-filename=") {
+            // the output is named with `-o`, which doesn't mix with `extra-filename`
+            if rustc_args.last() == Some(&"-C") {
+                rustc_args.pop();
+            }
+        } else if arg.ends_with(".rs")
+            || arg.starts_with("-Cextra-filename=")
+            // the script is a binary; test cfg is opted into with `#![test]`
+            || arg == "--test"
+            // wrappers (like clippy-driver) are given rustc's path
+            || Path::new(arg).file_stem().is_some_and(|s| s == "rustc")
+            || arg.starts_with("--emit")
+        {
+            continue;
+        } else {
+            rustc_args.push(&**arg);
+        }
+    }
+    rustc_args
+}
@@ -0,0 +1,132 @@
+//! `mod foo;` in a block.
+//!
+//! The file is looked for where it would be if the module were declared in the file the block is in
+//! (next to it, for a crate root or `mod.rs`, and in a directory named after it otherwise),
+//! and the script is given its absolute path with `#[path]`.
+use std::path::{Path, PathBuf};
+
+use syn::{visit_mut::VisitMut, Expr, Item, ItemMod};
+
+/// The file, and the directory its `mod foo;`s are in.
+pub fn module(file: PathBuf, root: bool) -> (PathBuf, PathBuf) {
+    let parent = file.parent().unwrap_or(Path::new("")).to_owned();
+    let dir = match root || file.file_name().is_some_and(|f| f == "mod.rs") {
+        true => parent,
+        false => parent.join(file.file_stem().unwrap_or_default()),
+    };
+    (file, dir)
+}
+
+/// The file the block is in, and the directory its modules are in.
+pub fn current() -> Option<(PathBuf, PathBuf)> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let file = cwd.join(super::call_site()?);
+    let root = super::args()
+        .into_iter()
+        .find(|a| a.ends_with(".rs"))
+        .is_some_and(|root| cwd.join(root) == file);
+    Some(module(file, root))
+}
+
+fn has_path(m: &ItemMod) -> bool {
+    m.attrs.iter().any(|a| a.path.is_ident("path"))
+}
+
+/// Points every `mod foo;` in a block at its file.
+pub struct Modules {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+    error: Option<String>,
+}
+
+impl Modules {
+    /// Resolve the modules in `body`, returning their files (and the files of the modules they declare).
+    pub fn apply(body: &mut Expr) -> Result<Vec<PathBuf>, String> {
+        let mut modules = Modules {
+            dir: current().map(|(_, dir)| dir).unwrap_or_default(),
+            files: vec![],
+            error: None,
+        };
+        modules.visit_expr_mut(body);
+        match modules.error {
+            Some(e) => Err(e),
+            None => Ok(modules.files),
+        }
+    }
+}
+
+impl VisitMut for Modules {
+    fn visit_item_mod_mut(&mut self, m: &mut ItemMod) {
+        if m.content.is_some() {
+            let dir = self.dir.join(m.ident.to_string());
+            let outer = std::mem::replace(&mut self.dir, dir);
+            syn::visit_mut::visit_item_mod_mut(self, m);
+            self.dir = outer;
+            return;
+        }
+        if has_path(m) {
+            return;
+        }
+        let name = m.ident.to_string();
+        let candidates = [
+            self.dir.join(format!("{name}.rs")),
+            self.dir.join(&name).join("mod.rs"),
+        ];
+        let Some(file) = candidates.iter().find(|f| f.exists()) else {
+            self.error.get_or_insert(format!(
+                "could not find module `{name}`: create {} or {}",
+                candidates[0].display(),
+                candidates[1].display()
+            ));
+            return;
+        };
+        let Some(path) = file.to_str() else {
+            self.error
+                .get_or_insert(format!("the path of module `{name}` is not utf8"));
+            return;
+        };
+        m.attrs.push(syn::parse_quote!(#[path = #path]));
+        // a file loaded with `#[path]` keeps its modules next to it, like a `mod.rs`
+        tree(
+            file,
+            file.parent().unwrap_or(Path::new("")),
+            &mut self.files,
+        );
+    }
+}
+
+/// `file`, and the files of the modules it declares, which are in `dir`.
+fn tree(file: &Path, dir: &Path, out: &mut Vec<PathBuf>) {
+    out.push(file.to_owned());
+    let Ok(items) = std::fs::read_to_string(file).map(|src| syn::parse_file(&src)) else {
+        return;
+    };
+    let Ok(items) = items.map(|f| f.items) else {
+        return;
+    };
+    items_tree(&items, dir, out);
+}
+
+fn items_tree(items: &[Item], dir: &Path, out: &mut Vec<PathBuf>) {
+    for item in items {
+        let Item::Mod(m) = item else { continue };
+        let name = m.ident.to_string();
+        match &m.content {
+            Some((_, content)) => items_tree(content, &dir.join(&name), out),
+            // not worth following
+            None if has_path(m) => {}
+            None => {
+                let file = [
+                    dir.join(format!("{name}.rs")),
+                    dir.join(&name).join("mod.rs"),
+                ]
+                .into_iter()
+                .find(|f| f.exists());
+                if let Some(file) = file {
+                    let (file, dir) = module(file, false);
+                    tree(&file, &dir, out);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Compiling scripts in the background.
+//!
+//! Jobs are keyed by the script's hash, so a block that was submitted ahead of time
+//! (see [`submit`]) is simply waited on when it's expanded.
+//! At most `EDG_JOBS` (or as many as there are cores) compilers run at once,
+//! and under cargo each of them (but the first) takes a token from its jobserver, so they don't oversubscribe the machine.
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    process::{Command, Output},
+    sync::{Condvar, Mutex, MutexGuard, OnceLock},
+};
+
+use jobserver::Client;
+
+struct Pool {
+    /// `None` while queued or running
+    jobs: BTreeMap<u64, Option<io::Result<Output>>>,
+    queue: VecDeque<(u64, Command)>,
+    running: usize,
+    /// rustc holds a jobserver token while it waits for us, which one job can use
+    implicit: bool,
+}
+
+static POOL: Mutex<Pool> = Mutex::new(Pool {
+    jobs: BTreeMap::new(),
+    queue: VecDeque::new(),
+    running: 0,
+    implicit: false,
+});
+static DONE: Condvar = Condvar::new();
+
+fn pool() -> MutexGuard<'static, Pool> {
+    POOL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn jobs() -> usize {
+    std::env::var("EDG_JOBS")
+        .ok()
+        .and_then(|j| j.parse().ok())
+        .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .max(1)
+}
+
+/// Cargo's jobserver, if we were given one.
+fn jobserver() -> Option<&'static Client> {
+    static CLIENT: OnceLock<Option<Client>> = OnceLock::new();
+    // SAFETY: the jobserver's descriptors are rustc's, which are open for as long as we're loaded
+    CLIENT
+        .get_or_init(|| unsafe { Client::from_env() })
+        .as_ref()
+}
+
+/// Start queued jobs, while there's room.
+/// Jobs that can't get a token stay queued until another one finishes, so something is always making progress.
+fn start(pool: &mut Pool) {
+    while pool.running < jobs() && !pool.queue.is_empty() {
+        let implicit = !pool.implicit;
+        let token = match jobserver() {
+            Some(c) if !implicit => match c.try_acquire() {
+                Ok(Some(token)) => Some(token),
+                _ => return,
+            },
+            _ => None,
+        };
+        let (hash, mut cmd) = pool.queue.pop_front().unwrap();
+        if let Some(c) = jobserver() {
+            c.configure(&mut cmd);
+        }
+        pool.implicit |= implicit;
+        pool.running += 1;
+        std::thread::spawn(move || {
+            let output = cmd.output();
+            drop(token);
+            let mut pool = self::pool();
+            pool.running -= 1;
+            pool.implicit &= !implicit;
+            pool.jobs.insert(hash, Some(output));
+            start(&mut pool);
+            DONE.notify_all();
+        });
+    }
+}
+
+/// Queue a compile, unless it's already queued.
+pub fn submit(hash: u64, cmd: Command) {
+    let mut pool = pool();
+    if pool.jobs.contains_key(&hash) {
+        return;
+    }
+    pool.jobs.insert(hash, None);
+    pool.queue.push_back((hash, cmd));
+    start(&mut pool);
+}
+
+/// Has this script been [`submit`]ted (and not yet waited on)?
+pub fn submitted(hash: u64) -> bool {
+    pool().jobs.contains_key(&hash)
+}
+
+/// Compile (or finish compiling) a script, which goes ahead of everything that's only been [`submit`]ted.
+pub fn compile(hash: u64, cmd: Command) -> io::Result<Output> {
+    let mut pool = pool();
+    match pool.queue.iter().position(|&(h, _)| h == hash) {
+        Some(i) => {
+            let job = pool.queue.remove(i).unwrap();
+            pool.queue.push_front(job);
+        }
+        None if !pool.jobs.contains_key(&hash) => {
+            pool.jobs.insert(hash, None);
+            pool.queue.push_front((hash, cmd));
+        }
+        None => {}
+    }
+    start(&mut pool);
+    loop {
+        if let Some(Some(_)) = pool.jobs.get(&hash) {
+            return pool.jobs.remove(&hash).unwrap().unwrap();
+        }
+        pool = DONE.wait(pool).unwrap_or_else(|e| e.into_inner());
+    }
+}
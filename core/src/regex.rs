@@ -0,0 +1,79 @@
+//! `edg::regex!`: a regex compiled to DFAs (by `regex-automata`) at compile time.
+//!
+//! The regex is built here, so a pattern that doesn't compile fails the build, and its two DFAs (the forward one
+//! finds where matches end, the reverse one where they start) are serialized in both byte orders.
+//! They're embedded as aligned statics, which `DFA::from_bytes` only has to check, not copy, so all the crate needs
+//! is `regex-automata`'s `dfa-search` (any 0.4 reads what another 0.4 wrote).
+use quote::quote;
+use regex_automata::{
+    dfa::{dense::DFA, regex::Regex},
+    nfa::thompson::NFA,
+};
+use syn::LitStr;
+
+pub fn regex(input: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let pattern = match syn::parse2::<LitStr>(input) {
+        Ok(p) => p,
+        Err(e) => return e.to_compile_error(),
+    };
+    let re = match build(&pattern.value()) {
+        Ok(re) => re,
+        Err(e) => return syn::Error::new_spanned(pattern, e).to_compile_error(),
+    };
+    let statics = [("FORWARD", re.forward()), ("REVERSE", re.reverse())].map(|(name, dfa)| {
+        let name = quote::format_ident!("{name}");
+        // without the padding that aligns them, which the statics take care of
+        let (le, pad) = dfa.to_bytes_little_endian();
+        let le = proc_macro2::Literal::byte_string(&le[pad..]);
+        let (be, pad) = dfa.to_bytes_big_endian();
+        let be = proc_macro2::Literal::byte_string(&be[pad..]);
+        quote! {
+            #[cfg(target_endian = "little")]
+            static #name: &Aligned<[u8]> = &Aligned { _align: [], bytes: *#le };
+            #[cfg(target_endian = "big")]
+            static #name: &Aligned<[u8]> = &Aligned { _align: [], bytes: *#be };
+        }
+    });
+    quote!({
+        #[repr(C)]
+        struct Aligned<B: ?Sized> {
+            _align: [u32; 0],
+            bytes: B,
+        }
+        #(#statics)*
+        static EDG: ::std::sync::LazyLock<
+            ::regex_automata::dfa::regex::Regex<::regex_automata::dfa::dense::DFA<&'static [u32]>>,
+        > = ::std::sync::LazyLock::new(|| {
+            let dfa = |bytes: &'static [u8]| {
+                ::regex_automata::dfa::dense::DFA::from_bytes(bytes)
+                    .expect("edg::regex!'s DFA is corrupt")
+                    .0
+            };
+            ::regex_automata::dfa::regex::Regex::builder()
+                .build_from_dfas(dfa(&FORWARD.bytes), dfa(&REVERSE.bytes))
+        });
+        &*EDG
+    })
+}
+
+/// The regex, or why it can't be one.
+/// DFAs only find where matches are, so a named group (which is only there to be captured) is an error.
+fn build(pattern: &str) -> Result<Regex<DFA<Vec<u32>>>, String> {
+    let chain = |e: &dyn std::error::Error| {
+        let mut msg = e.to_string();
+        let mut source = e.source();
+        while let Some(s) = source {
+            msg += &format!(": {s}");
+            source = s.source();
+        }
+        msg
+    };
+    let nfa = NFA::new(pattern).map_err(|e| chain(&e))?;
+    if let Some(name) = nfa.group_info().all_names().find_map(|(_, _, n)| n) {
+        return Err(format!(
+            "edg::regex! can't capture groups, so `(?<{name}>..)` would never be filled in; \
+             use `(?:..)`, or the `regex` crate for captures"
+        ));
+    }
+    Regex::new(pattern).map_err(|e| chain(&e))
+}
@@ -0,0 +1,407 @@
+//! Speculative compiles: when the first block of a crate is expanded, the crate's source is read for the others,
+//! which start compiling in the background (see [`super::pool`]), so they're ready (or closer to it) when they're
+//! expanded. `EDG_SPECULATE=0` turns this off.
+//!
+//! A script is laid out by where its tokens are in the file (see [`super::Layout`]), which tokens read from a string
+//! don't know, so the file is lexed alongside them to find out. Blocks that capture consts, declare modules or have
+//! blocks nested in them would be laid out from tokens that weren't read from the file, and are left alone,
+//! as are the ones under a `cfg` that's off. Guessing wrong only costs a compile.
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Item, Meta, NestedMeta};
+
+use super::{
+    capture::Captures,
+    delimiters, host,
+    modules::{module, Modules},
+    pool, Nested, Place,
+};
+
+/// The blocks compiled ahead of time, whose files are removed if they're never expanded.
+struct Speculated {
+    dir: PathBuf,
+    blocks: Vec<(u64, [PathBuf; 2])>,
+}
+
+thread_local! {
+    // dropped when rustc's thread exits, after the last block
+    static SPECULATED: RefCell<Option<Speculated>> = const { RefCell::new(None) };
+}
+
+impl Drop for Speculated {
+    fn drop(&mut self) {
+        for (hash, files) in &self.blocks {
+            // expanded blocks were waited on (and cleaned up after); the others are still the pool's.
+            // one that's still compiling leaves its binary behind
+            if !pool::submitted(*hash) {
+                continue;
+            }
+            // and another build may be using them
+            if let Ok(Some(mut lock)) = super::try_lock(&self.dir, *hash) {
+                lock.temporaries.extend(files.iter().cloned());
+            }
+        }
+    }
+}
+
+/// Start compiling the crate's blocks, if this is the first one to be expanded.
+pub fn start() {
+    static START: Once = Once::new();
+    if cfg!(feature = "runtime-fallback")
+        || super::metadata_only()
+        || std::env::var_os("EDG_SPECULATE").is_some_and(|v| v == "0")
+        // `edg-test` runs one block at a time
+        || !proc_macro::is_available()
+    {
+        return;
+    }
+    START.call_once(|| {
+        let blocks = files().iter().flat_map(|file| speculate(file)).collect();
+        SPECULATED.set(Some(Speculated {
+            dir: super::out_dir(),
+            blocks,
+        }));
+    });
+}
+
+/// Precompile the blocks of a file.
+fn speculate(file: &Path) -> Vec<(u64, [PathBuf; 2])> {
+    let Ok(src) = std::fs::read_to_string(file) else {
+        return vec![];
+    };
+    // as rustc reads it
+    let src = src
+        .strip_prefix('\u{feff}')
+        .unwrap_or(&src)
+        .replace("\r\n", "\n");
+    let Ok(tokens) = src.parse::<TokenStream>() else {
+        return vec![];
+    };
+    let mut lexer = Lexer {
+        src: &src,
+        pos: 0,
+        line: 1,
+        column: 1,
+        tokens: vec![],
+        found: vec![],
+    };
+    if lexer.stream(tokens, true).is_none() {
+        return vec![];
+    }
+    let mut out = vec![];
+    for (json, tokens, start) in std::mem::take(&mut lexer.found) {
+        let Ok((options, block)) = super::block(tokens, json) else {
+            continue;
+        };
+        if !Nested::find(&block.body).is_empty() {
+            continue;
+        }
+        let mut body = block.body.clone();
+        let unchanged = Captures::apply(&mut body, false).is_ok()
+            && Modules::apply(&mut body).is_ok()
+            && body.to_token_stream().to_string() == block.body.to_token_stream().to_string();
+        if !unchanged {
+            continue;
+        }
+        // the body is somewhere in the macro (after its attributes and the closure's head)
+        let mut texts = vec![];
+        flatten(block.body.to_token_stream(), &mut texts);
+        let Some(at) = (start..lexer.tokens.len()).find(|&i| {
+            lexer.tokens[i..]
+                .iter()
+                .map(|t| &t.text)
+                .take(texts.len())
+                .eq(&texts)
+        }) else {
+            continue;
+        };
+        let place = Place {
+            file: file.display().to_string(),
+            at: lexer.tokens[at..at + texts.len()]
+                .iter()
+                .map(|t| (t.line, t.column))
+                .collect(),
+        };
+        out.extend(super::precompile(&options, &block, Some(place)));
+    }
+    out
+}
+
+/// The crate's files (by the names rustc gives them): the root, and those of its `mod`s that are on.
+fn files() -> Vec<PathBuf> {
+    let Some(root) = super::args().into_iter().find(|a| a.ends_with(".rs")) else {
+        return vec![];
+    };
+    let mut files = vec![];
+    walk(module(root.into(), true), &mut files);
+    files
+}
+
+fn walk((file, dir): (PathBuf, PathBuf), files: &mut Vec<PathBuf>) {
+    let Some(items) = std::fs::read_to_string(&file)
+        .ok()
+        .and_then(|src| syn::parse_file(&src).ok())
+    else {
+        return;
+    };
+    modules(&items.items, &file, &dir, false, files);
+    files.push(file);
+}
+
+/// Follow the `mod foo;`s in `items`, which are in `file` (in an inline module, if `inline`).
+fn modules(items: &[Item], file: &Path, dir: &Path, inline: bool, files: &mut Vec<PathBuf>) {
+    for item in items {
+        let Item::Mod(m) = item else { continue };
+        let attrs = m.attrs.iter().filter_map(|a| a.parse_meta().ok());
+        if attrs.clone().any(|a| cfg_off(&a)) {
+            continue;
+        }
+        let path = attrs.into_iter().find_map(|a| match a {
+            Meta::NameValue(nv) if nv.path.is_ident("path") => match nv.lit {
+                syn::Lit::Str(s) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        });
+        let name = m.ident.to_string();
+        match (&m.content, path) {
+            (Some((_, items)), _) => modules(items, file, &dir.join(&name), true, files),
+            // relative to the file, or to the directory of the inline module it's in
+            (None, Some(path)) => {
+                let base = match inline {
+                    true => dir,
+                    false => file.parent().unwrap_or(Path::new("")),
+                };
+                walk(module(base.join(path), true), files)
+            }
+            (None, None) => {
+                let found = [
+                    dir.join(format!("{name}.rs")),
+                    dir.join(&name).join("mod.rs"),
+                ]
+                .into_iter()
+                .find(|f| f.exists());
+                if let Some(found) = found {
+                    walk(module(found, false), files)
+                }
+            }
+        }
+    }
+}
+
+/// Is this a `cfg` that's off?
+fn cfg_off(meta: &Meta) -> bool {
+    let Meta::List(l) = meta else { return false };
+    l.path.is_ident("cfg")
+        && matches!(&l.nested.iter().collect::<Vec<_>>()[..], [NestedMeta::Meta(p)] if !host::cfg(p))
+}
+
+/// Is this `edg::r!(..)` (`Some(false)`) or `edg::json!(..)` (`Some(true)`)?
+fn kind(trees: &[TokenTree]) -> Option<bool> {
+    let [TokenTree::Ident(edg), TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Ident(name), TokenTree::Punct(bang), TokenTree::Group(_), ..] =
+        trees
+    else {
+        return None;
+    };
+    (edg == "edg" && a.as_char() == ':' && b.as_char() == ':' && bang.as_char() == '!')
+        .then_some(())?;
+    match name.to_string().as_str() {
+        "r" => Some(false),
+        "json" => Some(true),
+        _ => None,
+    }
+}
+
+fn is_punct(tree: Option<&TokenTree>, c: char) -> bool {
+    matches!(tree, Some(TokenTree::Punct(p)) if p.as_char() == c)
+}
+
+/// The texts of a stream's tokens, as [`super::Layout`] writes them out.
+fn flatten(tokens: TokenStream, out: &mut Vec<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(g) => {
+                let (open, close) = delimiters(g.delimiter());
+                out.push(open.into());
+                flatten(g.stream(), out);
+                out.push(close.into());
+            }
+            TokenTree::Punct(p) => out.push(p.as_char().to_string()),
+            token => out.push(token.to_string()),
+        }
+    }
+}
+
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+/// Walks a file's tokens and its text together.
+struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+    /// in chars, like rustc's
+    column: usize,
+    /// every token of the file, flattened
+    tokens: Vec<Token>,
+    /// the blocks: whether they're `edg::json!`, their macro's tokens, and where those start in `tokens`
+    found: Vec<(bool, TokenStream, usize)>,
+}
+
+impl Lexer<'_> {
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        for c in self.src[self.pos..self.pos + bytes].chars() {
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                _ => self.column += 1,
+            }
+        }
+        self.pos += bytes;
+    }
+
+    /// Is a doc comment (which is a `#[doc]` to the tokens) next?
+    fn doc(&self) -> bool {
+        let rest = self.rest();
+        (rest.starts_with("///") && !rest.starts_with("////"))
+            || rest.starts_with("//!")
+            || (rest.starts_with("/**") && !rest.starts_with("/***") && !rest.starts_with("/**/"))
+            || rest.starts_with("/*!")
+    }
+
+    /// Skip a comment (the one that's next).
+    fn comment(&mut self) {
+        match self.rest().starts_with("//") {
+            true => self.advance(self.rest().find('\n').unwrap_or(self.rest().len())),
+            false => {
+                // they nest
+                let mut depth = 0;
+                let mut len = 0;
+                let rest = self.rest().as_bytes();
+                while len < rest.len() {
+                    if rest[len..].starts_with(b"/*") {
+                        depth += 1;
+                        len += 2;
+                    } else if rest[len..].starts_with(b"*/") {
+                        depth -= 1;
+                        len += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        len += 1;
+                    }
+                }
+                self.advance(len);
+            }
+        }
+    }
+
+    /// Skip whitespace and (non-doc) comments.
+    fn trivia(&mut self) {
+        loop {
+            let ws = self.rest().len() - self.rest().trim_start().len();
+            self.advance(ws);
+            if (self.rest().starts_with("//") || self.rest().starts_with("/*")) && !self.doc() {
+                self.comment();
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Take the token `text` from the source, which must be next.
+    fn token(&mut self, text: &str) -> Option<()> {
+        self.trivia();
+        self.rest().starts_with(text).then_some(())?;
+        self.tokens.push(Token {
+            text: text.into(),
+            line: self.line,
+            column: self.column,
+        });
+        self.advance(text.len());
+        Some(())
+    }
+
+    /// Walk a stream, noting the blocks in it (if `on`).
+    fn stream(&mut self, tokens: TokenStream, on: bool) -> Option<()> {
+        let trees = tokens.into_iter().collect::<Vec<_>>();
+        // an item under a `cfg` that's off, which ends with its body (or a `;`)
+        let mut off = false;
+        // where the next block's group is
+        let mut block = None;
+        // how far away a `macro_rules!`'s group is (its blocks aren't expanded as they're written)
+        let mut rules = None;
+        let mut i = 0;
+        while i < trees.len() {
+            self.trivia();
+            if is_punct(trees.get(i), '#') && self.doc() {
+                let (line, column) = (self.line, self.column);
+                self.comment();
+                let n = if is_punct(trees.get(i + 1), '!') {
+                    3
+                } else {
+                    2
+                };
+                let mut texts = vec![];
+                flatten(
+                    trees[i..(i + n).min(trees.len())].iter().cloned().collect(),
+                    &mut texts,
+                );
+                self.tokens
+                    .extend(texts.into_iter().map(|text| Token { text, line, column }));
+                i += n;
+                continue;
+            }
+            match (&trees[i], trees.get(i + 1)) {
+                (TokenTree::Punct(p), Some(TokenTree::Group(g)))
+                    if p.as_char() == '#'
+                        && syn::parse2::<Meta>(g.stream()).is_ok_and(|m| cfg_off(&m)) =>
+                {
+                    off = true
+                }
+                (TokenTree::Ident(id), _) if id == "macro_rules" => rules = Some(3),
+                _ => {}
+            }
+            if let Some(json) = kind(&trees[i..]) {
+                block = Some((json, i + 5));
+            }
+            match &trees[i] {
+                TokenTree::Group(g) => {
+                    let (open, close) = delimiters(g.delimiter());
+                    self.token(open)?;
+                    let macro_rules = rules.is_some_and(|r| r == 0);
+                    if let Some((json, _)) = block.filter(|&(_, at)| at == i && on && !off) {
+                        self.found.push((json, g.stream(), self.tokens.len()));
+                    }
+                    self.stream(g.stream(), on && !off && !macro_rules)?;
+                    self.token(close)?;
+                    off &= g.delimiter() != proc_macro2::Delimiter::Brace;
+                }
+                TokenTree::Punct(p) => {
+                    self.token(&p.as_char().to_string())?;
+                    off &= p.as_char() != ';';
+                }
+                token => self.token(&token.to_string())?,
+            }
+            rules = rules.and_then(|r: usize| r.checked_sub(1));
+            i += 1;
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,107 @@
+//! Statistics about the blocks of a crate, written to `target/edg/<crate>-<version>/stats-<unit>.json` as they're
+//! expanded (and summarized, with `EDG_STATS=1`, once rustc is done with the crate).
+//! Each of the crate's compilations (its lib, its tests, ..) has a file of its own, named after its `-C metadata`,
+//! so ones running at the same time don't overwrite each other's.
+use std::{cell::RefCell, path::PathBuf, time::Duration};
+
+/// How one block went.
+pub struct Block {
+    /// `file:line`
+    pub at: String,
+    pub hash: u64,
+    /// it wasn't compiled: its result was the one recorded by an earlier build, as it wasn't evaluated
+    /// (under rustdoc or `cargo check`), or the binary an earlier build compiled was run again
+    pub cached: bool,
+    /// its compile had been started ahead of time (see [`super::pool::submit`])
+    pub precompiled: bool,
+    pub compile: Duration,
+    pub run: Duration,
+    /// the size of the result
+    pub bytes: usize,
+}
+
+struct Stats {
+    file: PathBuf,
+    blocks: Vec<Block>,
+}
+
+thread_local! {
+    // dropped when rustc's thread exits, after the last block
+    static STATS: RefCell<Option<Stats>> = const { RefCell::new(None) };
+}
+
+/// Note how a block went.
+pub fn record(dir: PathBuf, block: Block) {
+    STATS.with_borrow_mut(|s| {
+        let stats = s.get_or_insert_with(|| Stats {
+            file: dir.join(format!("stats-{}.json", unit())),
+            blocks: vec![],
+        });
+        stats.blocks.push(block);
+        stats.write();
+    });
+}
+
+/// The compilation's name: its `-C metadata` (which cargo gives each one), or else the process.
+fn unit() -> String {
+    let args = super::args();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let metadata = match &**arg {
+            "-C" => args.next().and_then(|a| a.strip_prefix("metadata=")),
+            _ => arg.strip_prefix("-Cmetadata="),
+        };
+        if let Some(metadata) = metadata {
+            return metadata.into();
+        }
+    }
+    std::process::id().to_string()
+}
+
+impl Stats {
+    /// (Re)write the file, so it's there even if rustc exits without dropping [`STATS`].
+    fn write(&self) {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|b| {
+                serde_json::json!({
+                    "at": b.at,
+                    "hash": b.hash,
+                    "cached": b.cached,
+                    "precompiled": b.precompiled,
+                    "compile_ms": b.compile.as_millis() as u64,
+                    "run_ms": b.run.as_millis() as u64,
+                    "bytes": b.bytes,
+                })
+            })
+            .collect::<Vec<_>>();
+        _ = super::write(
+            &self.file,
+            serde_json::Value::from(blocks).to_string().as_bytes(),
+        );
+    }
+}
+
+impl Drop for Stats {
+    fn drop(&mut self) {
+        if !super::flag("EDG_STATS") {
+            return;
+        }
+        let sum = |f: fn(&Block) -> Duration| self.blocks.iter().map(f).sum::<Duration>();
+        let (compile, run) = (sum(|b| b.compile), sum(|b| b.run));
+        let hits = self.blocks.iter().filter(|b| b.cached).count();
+        eprintln!(
+            "note: edg: {} blocks in {:.1}s ({:.1}s compiling, {:.1}s running), {} cache hits and {} misses, {} precompiled, {} bytes of results; see {}",
+            self.blocks.len(),
+            (compile + run).as_secs_f32(),
+            compile.as_secs_f32(),
+            run.as_secs_f32(),
+            hits,
+            self.blocks.len() - hits,
+            self.blocks.iter().filter(|b| b.precompiled).count(),
+            self.blocks.iter().map(|b| b.bytes).sum::<usize>(),
+            self.file.display()
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! `edg::download!`'s cache, offline.
+use sha2::{Digest, Sha256};
+
+fn hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn download(input: &str) -> String {
+    edg_core::download(input.parse().unwrap()).to_string()
+}
+
+#[test]
+fn offline() {
+    let cache = std::env::temp_dir().join(format!("edg-downloads-{}", std::process::id()));
+    std::fs::create_dir_all(&cache).unwrap();
+    std::env::set_var("EDG_CACHE", &cache);
+    std::env::set_var("EDG_OFFLINE", "1");
+
+    let url = "https://example.com/data.txt";
+    std::fs::write(cache.join(format!("url-{}", hex(url.as_bytes()))), "cached").unwrap();
+    let out = download(&format!("{url:?} as &str"));
+    assert!(out.contains("include_str"), "{out}");
+    assert!(out.contains(&hex(url.as_bytes())), "{out}");
+    // unpinned, so it warns
+    assert!(out.contains("isn't pinned"), "{out}");
+
+    let pin = hex(b"pinned");
+    std::fs::write(cache.join(format!("sha256-{pin}")), "pinned").unwrap();
+    let out = download(&format!("\"https://example.com/other\", sha256 = {pin:?}"));
+    assert!(out.contains("include_bytes"), "{out}");
+    assert!(!out.contains("isn't pinned"), "{out}");
+
+    // a pinned file that doesn't match isn't used
+    let wrong = hex(b"something else");
+    std::fs::write(cache.join(format!("sha256-{wrong}")), "pinned").unwrap();
+    let out = download(&format!(
+        "\"https://example.com/other\", sha256 = {wrong:?}"
+    ));
+    assert!(out.contains("isn't cached"), "{out}");
+
+    let out = download("\"https://example.com/missing\"");
+    assert!(out.contains("compile_error"), "{out}");
+    assert!(out.contains("downloads are off"), "{out}");
+
+    _ = std::fs::remove_dir_all(cache);
+}
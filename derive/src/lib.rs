@@ -0,0 +1,79 @@
+//! Derive macros for [edg](https://docs.rs/edg). Use that crate instead.
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields};
+
+#[proc_macro_derive(ConstructTokens)]
+/// Derive `edg::ConstructTokens`, writing the type's name as it was declared.
+pub fn construct_tokens(input: TokenStream) -> TokenStream {
+    let DeriveInput {
+        ident,
+        mut generics,
+        data,
+        ..
+    } = parse_macro_input!(input);
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::edg::ConstructTokens));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = match data {
+        Data::Struct(s) => {
+            let (pat, write) = construct(&ident.to_string(), &s.fields);
+            quote!(let Self #pat = self; #write)
+        }
+        Data::Enum(e) => {
+            let arms = e.variants.iter().map(|v| {
+                let name = &v.ident;
+                let (pat, write) = construct(&format!("{ident}::{name}"), &v.fields);
+                quote!(Self::#name #pat => { #write })
+            });
+            match e.variants.is_empty() {
+                true => quote!(match *self {}),
+                false => quote!(match self { #(#arms)* }),
+            }
+        }
+        Data::Union(u) => {
+            return syn::Error::new(u.union_token.span, "unions can't be constructed")
+                .to_compile_error()
+                .into()
+        }
+    };
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics ::edg::ConstructTokens for #ident #ty_generics #where_clause {
+            fn construct(&self, out: &mut ::std::string::String) {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// A pattern binding the fields (as `f0`, `f1`, ..), and the code that writes `path { field: .. }`.
+fn construct(path: &str, fields: &Fields) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let binds = (0..fields.len())
+        .map(|i| format_ident!("f{i}"))
+        .collect::<Vec<_>>();
+    match fields {
+        Fields::Named(f) => {
+            let names = f.named.iter().map(|f| f.ident.as_ref().unwrap());
+            let writes = names.clone().zip(&binds).map(|(name, bind)| {
+                let name = format!("{name}: ");
+                quote!(out.push_str(#name); ::edg::ConstructTokens::construct(#bind, out); out.push_str(", ");)
+            });
+            let open = format!("{path} {{ ");
+            (
+                quote!({ #(#names: #binds),* }),
+                quote!(out.push_str(#open); #(#writes)* out.push('}');),
+            )
+        }
+        Fields::Unnamed(_) => {
+            let open = format!("{path}(");
+            (
+                quote!((#(#binds),*)),
+                quote!(out.push_str(#open); #(::edg::ConstructTokens::construct(#binds, out); out.push_str(", ");)* out.push(')');),
+            )
+        }
+        Fields::Unit => (quote!(), quote!(out.push_str(#path);)),
+    }
+}
@@ -0,0 +1,416 @@
+//! Procedural macros for [edg](https://docs.rs/edg). Use that crate instead.
+//!
+//! They're expanded by `edg-core`, which `edg-test` also uses, to run blocks outside of rustc.
+
+use proc_macro::TokenStream;
+
+#[proc_macro]
+/// Run a closure at compile time.
+/// This closure is completely isolated.
+/// You may return any data structure that implements [`serde::Serialize`](https://docs.rs/serde/latest/serde/trait.Serialize.html) and [`serde::Deserialize`](https://docs.rs/serde/latest/serde/trait.Deserialize.html).
+///
+/// ```
+/// let rand = edg::r! { || -> i32 {
+/// # mod rand { pub fn random() -> i32 { 4 } }
+///     rand::random()
+/// } };
+/// ```
+///
+/// As the closure is never called as one, `-> T { .. }` may be written instead of `|| -> T { .. }`.
+///
+/// ```
+/// let n = edg::r!(-> u32 { (1..=10).sum() });
+/// assert_eq!(n, 55);
+/// ```
+///
+/// If evaluation might fail for reasons outside your control (no network, say), give a `fallback`.
+/// When the block fails, the fallback is used instead and a warning is emitted.
+///
+/// ```
+/// let motd = edg::r! { || -> String {
+///     std::fs::read_to_string("/nonexistent/motd").unwrap()
+/// }, fallback = String::from("hello") };
+/// # assert_eq!(motd, "hello");
+/// ```
+///
+/// With `#![cfg(..)]`, the block is only evaluated when the predicate holds, and the `else` expression is used otherwise,
+/// so expensive generators can be put behind a feature. The predicate is evaluated like rustc would for the crate,
+/// so `unix`, `target_os = ".."`, `debug_assertions` and the like (of the target it's built for) work too.
+///
+/// ```
+/// let data = edg::r! { #![cfg(feature = "embed-data")] || -> Vec<u8> {
+///     std::fs::read("/usr/share/dict/words").unwrap()
+/// }, else = Vec::new() };
+/// assert!(data.is_empty());
+/// ```
+///
+/// The result is deserialized at run time (unless it's written out as a literal), which panics if it fails.
+/// `on_error = default` uses `T::default()` instead, and `on_error = result` makes the block evaluate to a
+/// `Result<T, edg::Error>`, for data that is only advisory.
+///
+/// ```
+/// let hint = edg::r! { || -> Vec<i32> { vec![-1] } as Vec<u32>, on_error = result };
+/// assert!(hint.is_err());
+/// let hint = edg::r! { || -> Vec<i32> { vec![-1] } as Vec<u32>, on_error = default };
+/// assert!(hint.is_empty());
+/// ```
+///
+/// Whatever the closure writes to stderr, and any warnings from compiling it, are reported as warnings.
+/// If it panics, the build fails with the panic's message, and where in your file it happened (with a backtrace).
+/// Set `EDG_WARN_AFTER` (to something like `10s`) to also be warned about every block that takes longer than that to
+/// compile and run, which keeps build time regressions visible in CI logs.
+/// How long each block took (and whether it was compiled, or its binary or result reused from an earlier build, whether
+/// it was compiled ahead of time, and how big its result was) is written to
+/// `target/edg/<crate>-<version>/stats-<unit>.json` (one for each of the crate's compilations: its lib, its tests, ..),
+/// and `EDG_STATS=1` prints a summary of it once the crate is compiled.
+///
+/// Primitives are emitted as literals, so they can even be used as array lengths.
+///
+/// ```
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// let buf = [0u8; edg::r! { || -> usize { 6 * 7 } }];
+/// assert_eq!(buf.len(), 42);
+/// # }
+/// ```
+///
+/// Big byte vectors (over 1MiB, or `EDG_SIDECAR_THRESHOLD` bytes) are written to a file in your target directory
+/// and pulled in with [`include_bytes!`], which is much kinder to the compiler than a huge json string.
+/// With `#![compress]` (or `EDG_COMPRESS=1`), any result is embedded deflated like that instead, and inflated at runtime.
+///
+/// ```
+/// let text = edg::r! { #![compress] || -> String { "edg ".repeat(1 << 16) } };
+/// assert_eq!(text.len(), 4 << 16);
+/// ```
+///
+/// Blocks may contain other `edg::r!` (or `edg::json!`) expressions, which are evaluated first.
+///
+/// ```
+/// let x = edg::r! { || -> u64 { edg::r!(|| -> u64 { 20 }) + 1 } };
+/// assert_eq!(x, 21);
+/// ```
+///
+/// `capture!(path)` copies a const from your crate into the block, as long as it's a literal.
+/// Paths starting with `crate::` are looked up from the crate root, and others in the current file.
+///
+/// ```ignore
+/// const LIMIT: u64 = 100;
+/// let x = edg::r! { || -> u64 { capture!(crate::LIMIT) * 3 } };
+/// assert_eq!(x, 300);
+/// ```
+///
+/// Bigger generators can be split into modules: `mod helpers;` in a block is looked for where it would be if it were
+/// declared in the file the block is in (so `src/helpers.rs` from `src/lib.rs`, and `src/foo/helpers.rs` from `src/foo.rs`).
+/// Modules declared in that file are next to it. The block is re-evaluated when any of them change.
+///
+/// ```ignore
+/// let table = edg::r! { || -> Vec<u32> {
+///     mod helpers;
+///     helpers::build()
+/// } };
+/// ```
+///
+/// `#![stdin("data.csv")]` (relative to your `Cargo.toml`), or `#![stdin(include_str!("data.csv"))]` (relative to the current file),
+/// pipes a file into the script's stdin, which keeps it out of the script. The block is re-evaluated when the file changes.
+///
+/// ```
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// let name = edg::r! { #![stdin("Cargo.toml")] || -> String {
+///     let manifest = std::io::read_to_string(std::io::stdin()).unwrap();
+///     manifest.lines().find(|l| l.starts_with("name")).unwrap().into()
+/// } };
+/// assert!(name.starts_with("name = \"edg"));
+/// # }
+/// ```
+///
+/// With `#![construct]`, the script writes the result out as an expression
+/// (with [`ConstructTokens`](https://docs.rs/edg/latest/edg/trait.ConstructTokens.html), which can be derived)
+/// instead of it being deserialized at runtime, so it also works in `const`s.
+///
+/// ```
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// const PRIMES: [(u8, &str); 3] = edg::r! { #![construct] || -> [(u8, &'static str); 3] {
+///     [(2, "two"), (3, "three"), (5, "five")]
+/// } };
+/// assert_eq!(PRIMES[2], (5, "five"));
+/// # }
+/// ```
+///
+/// Blocks may return an `impl Iterator<Item = T>`, which the script collects, and which becomes a `&'static [T]`:
+/// a literal one (which works in `static`s) if `T` is made of primitives, arrays, tuples and `&'static str`s,
+/// and one that's deserialized on first use otherwise.
+///
+/// ```
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// static SQUARES: &[u32] = edg::r!(-> impl Iterator<Item = u32> { (0..5u32).map(|x| x * x) });
+/// assert_eq!(SQUARES, [0, 1, 4, 9, 16]);
+/// # }
+/// let names = edg::r!(-> impl Iterator<Item = String> { ["a", "b"].into_iter().map(String::from) });
+/// assert_eq!(names, ["a", "b"]);
+/// ```
+///
+/// The return type may be left out if it only consists of primitives and standard library types.
+///
+/// ```
+/// let squares = edg::r! { || (0..4u8).map(|x| (x, x * x)).collect::<Vec<_>>() };
+/// assert_eq!(squares[3], (3, 9));
+/// ```
+///
+/// The host may deserialize into a different type than the one the closure returns with `as`,
+/// which keeps build-only types (and their crates) out of the final binary.
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Limits { min: u32, max: u32 }
+///
+/// let limits = edg::r! { || -> std::collections::HashMap<String, u32> {
+///     [("min".to_string(), 1), ("max".to_string(), 10)].into()
+/// } as Limits };
+/// assert_eq!((limits.min, limits.max), (1, 10));
+/// ```
+pub fn r(input: TokenStream) -> TokenStream {
+    edg_core::r(input.into()).into()
+}
+
+#[proc_macro]
+/// Run closures at compile time, binding their results with `let`.
+/// Each closure is evaluated once, and the result destructured, so one generator can produce several values.
+/// Inner attributes (like `#![miri]`) at the start apply to every binding.
+///
+/// ```
+/// edg::bind! {
+///     let (TABLE, CHECKSUM): (Vec<u8>, u64) = || {
+///         let table: Vec<u8> = (0..=255).collect();
+///         let sum = table.iter().map(|&x| x as u64).sum();
+///         (table, sum)
+///     };
+/// }
+/// assert_eq!(TABLE.len(), 256);
+/// assert_eq!(CHECKSUM, 32640);
+/// ```
+pub fn bind(input: TokenStream) -> TokenStream {
+    edg_core::bind(input.into()).into()
+}
+
+#[proc_macro]
+/// Run a closure at compile time, without naming its return type.
+/// The result is transported as a [`serde_json::Value`](https://docs.rs/serde_json/latest/serde_json/enum.Value.html).
+///
+/// ```
+/// let config = edg::json! { || {
+///     std::collections::BTreeMap::from([("name", "edg"), ("version", "0.1.0")])
+/// } };
+/// assert_eq!(config["name"], "edg");
+/// ```
+pub fn json(input: TokenStream) -> TokenStream {
+    edg_core::json(input.into()).into()
+}
+
+#[proc_macro]
+/// Generate a lookup table at compile time.
+/// The closure is called with every index (as a `usize`), and the results are emitted as a `static` array literal,
+/// so nothing is deserialized at run time.
+/// An `#[inline]` accessor function, named after the table in lowercase, is emitted alongside it.
+/// The element type must be a primitive, or made of arrays and tuples of them.
+///
+/// ```
+/// edg::table!(pub CRC32: [u32; 256] = |i| {
+///     let mut c = i as u32;
+///     for _ in 0..8 {
+///         c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+///     }
+///     c
+/// });
+/// assert_eq!(CRC32[1], 0x77073096);
+/// assert_eq!(crc32(255), 0x2D02EF8D);
+/// ```
+pub fn table(input: TokenStream) -> TokenStream {
+    edg_core::table(input.into()).into()
+}
+
+#[proc_macro]
+/// Evaluate a closure at compile time, and put the result in a `static`, which is constructed as a constant:
+/// nothing is deserialized (or allocated) at run time, and the data ends up in the binary's read-only data.
+/// Primitives (and arrays, tuples and `&'static str`s of them) are written out as they are,
+/// and anything else is `#![construct]`ed (so it must implement [`ConstructTokens`](https://docs.rs/edg/latest/edg/trait.ConstructTokens.html)).
+///
+/// ```
+/// edg::static_!(pub PRIMES: [u16; 5] = || {
+///     let mut primes = vec![];
+///     let mut n = 2;
+///     while primes.len() < 5 {
+///         if (2..n).all(|d| n % d != 0) {
+///             primes.push(n);
+///         }
+///         n += 1;
+///     }
+///     <[u16; 5]>::try_from(primes).unwrap()
+/// });
+/// assert_eq!(PRIMES, [2, 3, 5, 7, 11]);
+/// edg::static_!(UNITS: &[(&str, f32)] = || &[("m", 1.0), ("cm", 0.01)]);
+/// assert_eq!(UNITS[1].0, "cm");
+/// ```
+pub fn static_(input: TokenStream) -> TokenStream {
+    edg_core::static_(input.into()).into()
+}
+
+#[proc_macro]
+/// Generate files at compile time.
+/// The closure is given a directory (managed by edg, inside your target directory) to write files into,
+/// and the macro expands to that directory's path as a string literal, for use with [`include!`] and friends.
+/// A literal has nowhere to put warnings, so the block's (like a slow compile's) aren't shown.
+///
+/// ```
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// const SHADER: &str = include_str!(concat!(
+///     edg::generate! { |out: &std::path::Path| {
+///         let body = (0..4).map(|i| format!("out[{i}] = in[{i}] * 2.0;\n")).collect::<String>();
+///         std::fs::write(out.join("double.glsl"), body).unwrap();
+///     } },
+///     "/double.glsl"
+/// ));
+/// assert!(SHADER.starts_with("out[0] = in[0] * 2.0;"));
+/// # }
+/// ```
+pub fn generate(input: TokenStream) -> TokenStream {
+    edg_core::generate(input.into()).into()
+}
+
+#[proc_macro]
+/// Read an environment variable at compile time, parse it (with [`FromStr`](std::str::FromStr)), and embed the result.
+/// An optional validator is given the parsed value and returns a `bool`, or a `Result<(), impl Display>`.
+/// If the variable is unset, doesn't parse, or doesn't validate, the build fails.
+///
+/// ```
+/// let major = edg::env!("CARGO_PKG_VERSION_MAJOR" as u32, |v| v < 100);
+/// assert_eq!(major, 0);
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// let buf = [0u8; edg::env!("CARGO_PKG_VERSION_MINOR" as usize)];
+/// assert_eq!(buf.len(), 1);
+/// # }
+/// ```
+pub fn env(input: TokenStream) -> TokenStream {
+    edg_core::env(input.into()).into()
+}
+
+#[proc_macro]
+/// Describe the build, as an [`edg::BuildInfo`](https://docs.rs/edg/latest/edg/struct.BuildInfo.html):
+/// the git commit (and whether the work tree had changes), when it happened, the `rustc` that did it, the host's triple,
+/// and the crate's enabled features. It's written out as a constant, so it can go in `const`s, and its `Display`
+/// is made for `--version`.
+///
+/// ```
+/// # #[cfg(not(feature = "runtime-fallback"))] {
+/// const BUILD: edg::BuildInfo = edg::buildinfo!();
+/// assert!(BUILD.rustc.starts_with("rustc "));
+/// println!("edg {} ({BUILD})", env!("CARGO_PKG_VERSION"));
+/// # }
+/// ```
+pub fn buildinfo(input: TokenStream) -> TokenStream {
+    edg_core::buildinfo(input.into()).into()
+}
+
+#[proc_macro]
+/// Download a file at compile time, and embed it: as a `&'static [u8]`, as a `&'static str` (`as &str`),
+/// or deserialized from json (`as T`).
+/// Downloads are cached (in `EDG_CACHE`, or `$CARGO_HOME/edg/downloads`), so they survive `cargo clean`.
+/// Pin one with its `sha256` to have it checked; without that, a warning says what it is.
+/// `EDG_OFFLINE=1` (or `CARGO_NET_OFFLINE=true`) fails the build instead of downloading what isn't cached.
+/// Downloads go through `curl`.
+///
+/// ```ignore
+/// // the first build warns, with the sha256 to pin it to
+/// static LICENSE: &str = edg::download!("https://raw.githubusercontent.com/bend-n/edg/main/LICENSE" as &str);
+///
+/// #[derive(serde::Deserialize)]
+/// struct Release { tag_name: String }
+/// let release = edg::download!("https://example.com/release.json" as Release, sha256 = "f9d8..1310");
+/// ```
+pub fn download(input: TokenStream) -> TokenStream {
+    edg_core::download(input.into()).into()
+}
+
+#[proc_macro]
+/// Compile a regex at compile time, with [`regex-automata`](https://docs.rs/regex-automata), which the crate has to
+/// depend on (only its `dfa-search` feature is needed: `default-features = false, features = ["dfa-search"]`).
+/// A pattern that doesn't compile fails the build; one that does is embedded as DFAs, which are only checked
+/// (not built) when the regex is first used.
+///
+/// It's a [`regex_automata::dfa::regex::Regex`](https://docs.rs/regex-automata/latest/regex_automata/dfa/regex/struct.Regex.html),
+/// which finds where matches are, but doesn't capture groups, so named groups (`(?<year>..)`) are an error.
+///
+/// ```
+/// let date = edg::regex!(r"\d{4}-\d{2}");
+/// assert!(date.is_match("released 2024-06"));
+/// assert!(!date.is_match("released in june"));
+/// let m = date.find("released 2024-06").unwrap();
+/// assert_eq!(m.range(), 9..16);
+/// ```
+///
+/// ```compile_fail
+/// let unclosed = edg::regex!("(a");
+/// ```
+///
+/// ```compile_fail
+/// let date = edg::regex!(r"(?<year>\d{4})-(?<month>\d{2})");
+/// ```
+pub fn regex(input: TokenStream) -> TokenStream {
+    edg_core::regex(input.into()).into()
+}
+
+#[proc_macro]
+/// Read a file at compile time, process it with a closure, and embed the result.
+/// The path is relative to your `Cargo.toml`, or (as an `include_str!`/`include_bytes!`) to the current file, like `#![stdin]`'s.
+/// The closure takes the contents as a `&str`, or as a `&[u8]` if that's what its argument is annotated with,
+/// and is re-evaluated whenever the file changes.
+///
+/// ```
+/// let deps = edg::transform!("Cargo.toml", |manifest: &str| -> usize {
+///     manifest.lines().skip_while(|l| *l != "[dependencies]").skip(1).take_while(|l| !l.is_empty()).count()
+/// });
+/// assert!(deps > 0);
+/// let size = edg::transform!(include_bytes!("lib.rs"), |src: &[u8]| -> usize { src.len() });
+/// assert!(size > 0);
+/// ```
+pub fn transform(input: TokenStream) -> TokenStream {
+    edg_core::transform(input.into()).into()
+}
+
+#[proc_macro]
+/// Assert something at compile time.
+/// The closure returns a `bool`, or a `Result<(), impl Display>`, and the build fails (with the message, if given) when it isn't `true`/`Ok`.
+/// It expands to nothing, so it works anywhere an item or statement does.
+///
+/// ```
+/// edg::assert!(|| "0.1.0".split('.').count() == 3, "versions have three parts");
+/// edg::assert!(|| -> Result<(), std::num::ParseIntError> { "42".parse::<u8>().map(drop) });
+/// ```
+///
+/// With the `runtime-fallback` feature, nothing is checked.
+pub fn assert(input: TokenStream) -> TokenStream {
+    edg_core::assert(input.into()).into()
+}
+
+#[proc_macro_attribute]
+/// Precompute a function for a set of inputs.
+/// The function is evaluated (at compile time) for everything the attribute's argument iterates over,
+/// and the results become a `match`, which falls back to the original body for any other input.
+/// Functions with several arguments are given tuples of them.
+/// Both the arguments and the return type must be primitives, or arrays and tuples of them.
+///
+/// ```
+/// #[edg::memo(0..=255)]
+/// fn popcount(i: u8) -> u8 {
+///     i.count_ones() as u8
+/// }
+/// assert_eq!(popcount(0b1011), 3);
+///
+/// #[edg::memo([(1, 1), (6, 4)])]
+/// fn gcd(a: u64, b: u64) -> u64 {
+///     if b == 0 { a } else { gcd(b, a % b) }
+/// }
+/// assert_eq!(gcd(6, 4), 2);
+/// assert_eq!(gcd(9, 6), 3);
+/// ```
+pub fn memo(attr: TokenStream, item: TokenStream) -> TokenStream {
+    edg_core::memo(attr.into(), item.into()).into()
+}
@@ -0,0 +1,77 @@
+use core::fmt;
+
+/// What [`buildinfo!`](crate::buildinfo) found out about the build.
+///
+/// `Display` writes it the way `--version`s say it:
+/// `1a2b3c4-dirty, built 2026-01-02 03:04:05 UTC with rustc 1.90.0 (1159e78c4 2025-09-14) on x86_64-unknown-linux-gnu`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuildInfo {
+    /// the commit `HEAD` was at, if the crate is in a git repository
+    pub commit: Option<&'static str>,
+    /// the work tree had changes that weren't committed
+    pub dirty: bool,
+    /// when the crate was compiled, in seconds since the unix epoch (`SOURCE_DATE_EPOCH`, if it's set)
+    pub timestamp: u64,
+    /// what `rustc --version` says
+    pub rustc: &'static str,
+    /// the triple of the machine it was compiled on
+    pub host: &'static str,
+    /// the crate's enabled features, sorted
+    pub features: &'static [&'static str],
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(commit) = self.commit {
+            let dirty = if self.dirty { "-dirty" } else { "" };
+            write!(f, "{}{dirty}, ", &commit[..commit.len().min(7)])?;
+        }
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let (days, secs) = ((self.timestamp / 86400) as i64, self.timestamp % 86400);
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        write!(
+            f,
+            "built {year}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC with {} on {}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            self.rustc,
+            self.host
+        )?;
+        if !self.features.is_empty() {
+            f.write_str(", features: ")?;
+            for (i, feature) in self.features.iter().enumerate() {
+                let comma = if i == 0 { "" } else { ", " };
+                write!(f, "{comma}{feature}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::ConstructTokens for BuildInfo {
+    fn construct(&self, out: &mut String) {
+        use std::fmt::Write;
+
+        out.push_str("::edg::BuildInfo { commit: ");
+        self.commit.construct(out);
+        write!(out, ", dirty: {}, timestamp: ", self.dirty).unwrap();
+        self.timestamp.construct(out);
+        out.push_str(", rustc: ");
+        self.rustc.construct(out);
+        out.push_str(", host: ");
+        self.host.construct(out);
+        out.push_str(", features: ");
+        self.features.construct(out);
+        out.push_str(" }");
+    }
+}
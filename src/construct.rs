@@ -0,0 +1,210 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Write,
+};
+
+/// Values that can write an expression that constructs them.
+///
+/// `#![construct]` blocks print their result with this instead of serializing it,
+/// so the host gets plain constructor code: no deserialization at runtime, and no serde_json in the binary.
+/// The derive writes the type's name as it was declared, so it has to be in scope where the block is.
+///
+/// ```
+/// #[derive(edg::ConstructTokens)]
+/// struct Point { x: i32, y: Option<&'static str> }
+///
+/// let mut out = String::new();
+/// edg::ConstructTokens::construct(&Point { x: -1, y: Some("up") }, &mut out);
+/// assert_eq!(out, r#"Point { x: (-1i32), y: ::core::option::Option::Some("up"), }"#);
+/// ```
+pub trait ConstructTokens {
+    /// Write an expression that evaluates to `self`.
+    fn construct(&self, out: &mut String);
+}
+
+macro_rules! integer {
+    ($($t:ty)+) => {$(
+        impl ConstructTokens for $t {
+            fn construct(&self, out: &mut String) {
+                #[allow(unused_comparisons)]
+                match *self < 0 {
+                    true => write!(out, "({self}{})", stringify!($t)),
+                    false => write!(out, "{self}{}", stringify!($t)),
+                }
+                .unwrap()
+            }
+        }
+    )+};
+}
+integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+macro_rules! float {
+    ($($t:ident)+) => {$(
+        impl ConstructTokens for $t {
+            fn construct(&self, out: &mut String) {
+                match *self {
+                    x if x.is_nan() => write!(out, "{}::NAN", stringify!($t)),
+                    x if x.is_infinite() && x > 0.0 => write!(out, "{}::INFINITY", stringify!($t)),
+                    x if x.is_infinite() => write!(out, "{}::NEG_INFINITY", stringify!($t)),
+                    // debug keeps the `.0`, and all the digits
+                    x if x.is_sign_negative() => write!(out, "({x:?}{})", stringify!($t)),
+                    x => write!(out, "{x:?}{}", stringify!($t)),
+                }
+                .unwrap()
+            }
+        }
+    )+};
+}
+float!(f32 f64);
+
+impl ConstructTokens for bool {
+    fn construct(&self, out: &mut String) {
+        write!(out, "{self}").unwrap()
+    }
+}
+
+impl ConstructTokens for char {
+    fn construct(&self, out: &mut String) {
+        write!(out, "{self:?}").unwrap()
+    }
+}
+
+impl ConstructTokens for () {
+    fn construct(&self, out: &mut String) {
+        out.push_str("()")
+    }
+}
+
+impl ConstructTokens for &str {
+    fn construct(&self, out: &mut String) {
+        write!(out, "{self:?}").unwrap()
+    }
+}
+
+impl ConstructTokens for String {
+    fn construct(&self, out: &mut String) {
+        write!(out, "::std::string::String::from({self:?})").unwrap()
+    }
+}
+
+impl<T: ConstructTokens> ConstructTokens for &[T] {
+    fn construct(&self, out: &mut String) {
+        out.push_str("&[");
+        list(*self, out);
+        out.push(']');
+    }
+}
+
+impl<T: ConstructTokens, const N: usize> ConstructTokens for [T; N] {
+    fn construct(&self, out: &mut String) {
+        out.push('[');
+        list(self, out);
+        out.push(']');
+    }
+}
+
+impl<T: ConstructTokens> ConstructTokens for Vec<T> {
+    fn construct(&self, out: &mut String) {
+        out.push_str("::std::vec![");
+        list(self, out);
+        out.push(']');
+    }
+}
+
+impl<T: ConstructTokens> ConstructTokens for Box<T> {
+    fn construct(&self, out: &mut String) {
+        out.push_str("::std::boxed::Box::new(");
+        (**self).construct(out);
+        out.push(')');
+    }
+}
+
+impl<T: ConstructTokens> ConstructTokens for Option<T> {
+    fn construct(&self, out: &mut String) {
+        match self {
+            Some(x) => {
+                out.push_str("::core::option::Option::Some(");
+                x.construct(out);
+                out.push(')');
+            }
+            None => out.push_str("::core::option::Option::None"),
+        }
+    }
+}
+
+impl<T: ConstructTokens, E: ConstructTokens> ConstructTokens for Result<T, E> {
+    fn construct(&self, out: &mut String) {
+        let (variant, x): (_, &dyn ConstructTokens) = match self {
+            Ok(x) => ("Ok", x),
+            Err(e) => ("Err", e),
+        };
+        write!(out, "::core::result::Result::{variant}(").unwrap();
+        x.construct(out);
+        out.push(')');
+    }
+}
+
+macro_rules! set {
+    ($($t:ident)+) => {$(
+        impl<T: ConstructTokens> ConstructTokens for $t<T> {
+            fn construct(&self, out: &mut String) {
+                out.push_str(concat!("::std::collections::", stringify!($t), "::from(["));
+                list(self, out);
+                out.push_str("])");
+            }
+        }
+    )+};
+}
+set!(BTreeSet HashSet);
+
+macro_rules! map {
+    ($($t:ident)+) => {$(
+        impl<K: ConstructTokens, V: ConstructTokens> ConstructTokens for $t<K, V> {
+            fn construct(&self, out: &mut String) {
+                out.push_str(concat!("::std::collections::", stringify!($t), "::from(["));
+                for (k, v) in self {
+                    out.push('(');
+                    k.construct(out);
+                    out.push_str(", ");
+                    v.construct(out);
+                    out.push_str("), ");
+                }
+                out.push_str("])");
+            }
+        }
+    )+};
+}
+map!(BTreeMap HashMap);
+
+macro_rules! tuple {
+    ($($t:ident)+) => {
+        impl<$($t: ConstructTokens),+> ConstructTokens for ($($t,)+) {
+            #[allow(non_snake_case)]
+            fn construct(&self, out: &mut String) {
+                let ($($t,)+) = self;
+                out.push('(');
+                $($t.construct(out); out.push_str(", ");)+
+                out.push(')');
+            }
+        }
+    };
+}
+tuple!(A);
+tuple!(A B);
+tuple!(A B C);
+tuple!(A B C D);
+tuple!(A B C D E);
+tuple!(A B C D E F);
+tuple!(A B C D E F G);
+tuple!(A B C D E F G H);
+tuple!(A B C D E F G H I);
+tuple!(A B C D E F G H I J);
+tuple!(A B C D E F G H I J K);
+tuple!(A B C D E F G H I J K L);
+
+fn list<'a, T: ConstructTokens + 'a>(items: impl IntoIterator<Item = &'a T>, out: &mut String) {
+    for item in items {
+        item.construct(out);
+        out.push_str(", ");
+    }
+}
@@ -0,0 +1,57 @@
+use std::{fmt::Display, panic::Location, sync::RwLock};
+
+/// How a block failed, for the closure given to [`on_failure`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct Failure<'a> {
+    /// what it panicked with, or the error's `Display`
+    pub message: &'a str,
+    /// it was an `Err` given to [`ok`], not a panic
+    pub err: bool,
+    /// where it panicked, or where `ok` was called
+    pub location: Option<&'a Location<'a>>,
+}
+
+type Explain = Box<dyn Fn(&Failure) -> Option<String> + Send + Sync>;
+
+static EXPLAIN: RwLock<Option<Explain>> = RwLock::new(None);
+
+/// Rephrase how the block this is called in fails: when it panics (or gives [`ok`] an `Err`), whatever `f`
+/// returns becomes the compile error, instead of edg's message, location and backtrace.
+/// Returning `None` leaves the failure as it was.
+///
+/// It's meant for macros that are built on edg, so that their users see what went wrong in their terms.
+/// Calling it again replaces the previous closure.
+///
+/// ```ignore
+/// macro_rules! migration {
+///     ($file:literal) => {
+///         edg::r!(-> String {
+///             edg::on_failure(|f| Some(format!("schema mismatch in {}: {}", $file, f.message)));
+///             edg::ok(check(include_str!($file)))
+///         })
+///     };
+/// }
+/// ```
+pub fn on_failure(f: impl Fn(&Failure) -> Option<String> + Send + Sync + 'static) {
+    *EXPLAIN.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(f));
+}
+
+/// The error of an `Err`, which [`ok`] panics with.
+pub(crate) struct Errored(pub(crate) String);
+
+/// The value of an `Ok`; an `Err` fails the block, with the error as its message
+/// (which [`on_failure`]'s closure gets as such).
+#[track_caller]
+pub fn ok<T, E: Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(v) => v,
+        Err(e) => std::panic::panic_any(Errored(e.to_string())),
+    }
+}
+
+/// The failure, as the closure given to [`on_failure`] puts it, if there is one.
+pub(crate) fn explain(failure: &Failure) -> Option<String> {
+    let explain = EXPLAIN.read().unwrap_or_else(|e| e.into_inner());
+    explain.as_ref()?(failure)
+}
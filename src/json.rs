@@ -0,0 +1,774 @@
+//! The json results travel in: serde_json's, except that
+//! - floats json has no numbers for (NaN and the infinities) are written as the strings `"NaN"`, `"inf"` and `"-inf"`,
+//! - 128-bit integers that don't fit in 64 bits (which not every json reader takes exactly) are written as strings,
+//! - maps with keys that can't be object keys (anything but strings, integers and bools) are written as
+//!   flat arrays of keys and values, `[k, v, k, v]`,
+//!
+//! which [`Deserializer`] takes wherever a float, a 128-bit integer (or a map) is expected.
+use serde::{
+    de::{self, DeserializeSeed, Visitor},
+    ser::{self, Serialize},
+};
+use std::fmt::{self, Display};
+
+/// Why a value couldn't be serialized, and where in it.
+#[derive(Debug)]
+pub struct Error {
+    msg: String,
+    /// like `.points[3].x`
+    path: Option<String>,
+}
+
+impl Error {
+    /// Note where the error happened, unless something deeper already did.
+    fn at(mut self, path: &str) -> Self {
+        self.path.get_or_insert_with(|| path.to_owned());
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_deref() {
+            Some(path) if !path.is_empty() => write!(f, "{} (at `{path}`)", self.msg),
+            _ => f.write_str(&self.msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self {
+            msg: msg.to_string(),
+            path: None,
+        }
+    }
+}
+
+/// Serialize a value.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let data = value.serialize(Serializer {
+        path: String::new(),
+    })?;
+    serde_json::to_string(&data).map_err(ser::Error::custom)
+}
+
+/// A serialized value, before it's written out.
+enum Data {
+    Null,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Str(String),
+    Seq(Vec<Data>),
+    Map(Vec<(Data, Data)>),
+}
+
+impl Serialize for Data {
+    fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+        match self {
+            Self::Null => s.serialize_unit(),
+            Self::Bool(b) => s.serialize_bool(*b),
+            Self::I64(n) => s.serialize_i64(*n),
+            Self::U64(n) => s.serialize_u64(*n),
+            Self::F32(n) => s.serialize_f32(*n),
+            Self::F64(n) => s.serialize_f64(*n),
+            Self::Str(x) => s.serialize_str(x),
+            Self::Seq(items) => {
+                let mut seq = s.serialize_seq(Some(items.len()))?;
+                items.iter().try_for_each(|i| seq.serialize_element(i))?;
+                seq.end()
+            }
+            Self::Map(entries) if !entries.iter().all(|(k, _)| k.key()) => {
+                let mut seq = s.serialize_seq(Some(entries.len() * 2))?;
+                entries.iter().try_for_each(|(k, v)| {
+                    seq.serialize_element(k)?;
+                    seq.serialize_element(v)
+                })?;
+                seq.end()
+            }
+            Self::Map(entries) => {
+                let mut map = s.serialize_map(Some(entries.len()))?;
+                entries
+                    .iter()
+                    .try_for_each(|(k, v)| map.serialize_entry(k, v))?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl Data {
+    /// Can it be the key of a json object?
+    fn key(&self) -> bool {
+        matches!(
+            self,
+            Self::Str(_) | Self::I64(_) | Self::U64(_) | Self::Bool(_)
+        )
+    }
+}
+
+/// A float, or what stands in for it if json can't have it.
+fn float<F: Into<f64> + Copy>(f: F, data: fn(F) -> Data) -> Data {
+    match f.into() {
+        x if x.is_nan() => Data::Str("NaN".into()),
+        x if x == f64::INFINITY => Data::Str("inf".into()),
+        x if x == f64::NEG_INFINITY => Data::Str("-inf".into()),
+        _ => data(f),
+    }
+}
+
+/// Serializes into [`Data`], keeping track of where it is for the errors.
+struct Serializer {
+    path: String,
+}
+
+impl Serializer {
+    fn child<T: Serialize + ?Sized>(path: String, value: &T) -> Result<Data, Error> {
+        value
+            .serialize(Serializer { path: path.clone() })
+            .map_err(|e| e.at(&path))
+    }
+}
+
+/// `{ variant: data }`, the way serde_json writes enums.
+fn variant(name: &str, data: Data) -> Data {
+    Data::Map(vec![(Data::Str(name.into()), data)])
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Data;
+    type Error = Error;
+    type SerializeSeq = Seq;
+    type SerializeTuple = Seq;
+    type SerializeTupleStruct = Seq;
+    type SerializeTupleVariant = Seq;
+    type SerializeMap = Map;
+    type SerializeStruct = Map;
+    type SerializeStructVariant = Map;
+
+    fn serialize_bool(self, v: bool) -> Result<Data, Error> {
+        Ok(Data::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Data, Error> {
+        Ok(Data::I64(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Data, Error> {
+        Ok(Data::I64(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Data, Error> {
+        Ok(Data::I64(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Data, Error> {
+        Ok(Data::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Data, Error> {
+        Ok(i64::try_from(v).map_or_else(|_| Data::Str(v.to_string()), Data::I64))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Data, Error> {
+        Ok(Data::U64(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Data, Error> {
+        Ok(Data::U64(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Data, Error> {
+        Ok(Data::U64(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Data, Error> {
+        Ok(Data::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Data, Error> {
+        Ok(u64::try_from(v).map_or_else(|_| Data::Str(v.to_string()), Data::U64))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Data, Error> {
+        Ok(float(v, Data::F32))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Data, Error> {
+        Ok(float(v, Data::F64))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Data, Error> {
+        Ok(Data::Str(v.into()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Data, Error> {
+        Ok(Data::Str(v.into()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Data, Error> {
+        Ok(Data::Seq(v.iter().map(|&b| Data::U64(b.into())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Data, Error> {
+        Ok(Data::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Data, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Data, Error> {
+        Ok(Data::Null)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Data, Error> {
+        Ok(Data::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        v: &'static str,
+    ) -> Result<Data, Error> {
+        Ok(Data::Str(v.into()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Data, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        v: &'static str,
+        value: &T,
+    ) -> Result<Data, Error> {
+        let data = Self::child(format!("{}.{v}", self.path), value)?;
+        Ok(variant(v, data))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Seq, Error> {
+        Ok(Seq {
+            path: self.path,
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Seq, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Seq, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        v: &'static str,
+        len: usize,
+    ) -> Result<Seq, Error> {
+        Ok(Seq {
+            path: format!("{}.{v}", self.path),
+            variant: Some(v),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Map, Error> {
+        Ok(Map {
+            path: self.path,
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Map, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        v: &'static str,
+        len: usize,
+    ) -> Result<Map, Error> {
+        Ok(Map {
+            path: format!("{}.{v}", self.path),
+            variant: Some(v),
+            entries: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+}
+
+struct Seq {
+    path: String,
+    variant: Option<&'static str>,
+    items: Vec<Data>,
+}
+
+impl Seq {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let path = format!("{}[{}]", self.path, self.items.len());
+        self.items.push(Serializer::child(path, value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Data, Error> {
+        let seq = Data::Seq(self.items);
+        Ok(match self.variant {
+            Some(v) => variant(v, seq),
+            None => seq,
+        })
+    }
+}
+
+macro_rules! seq {
+    ($($trait:ident $method:ident),+) => {$(
+        impl ser::$trait for Seq {
+            type Ok = Data;
+            type Error = Error;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+                self.push(value)
+            }
+
+            fn end(self) -> Result<Data, Error> {
+                self.finish()
+            }
+        }
+    )+};
+}
+seq!(
+    SerializeSeq serialize_element,
+    SerializeTuple serialize_element,
+    SerializeTupleStruct serialize_field,
+    SerializeTupleVariant serialize_field
+);
+
+struct Map {
+    path: String,
+    variant: Option<&'static str>,
+    entries: Vec<(Data, Data)>,
+    /// waiting for its value
+    key: Option<Data>,
+}
+
+impl Map {
+    fn finish(self) -> Result<Data, Error> {
+        let map = Data::Map(self.entries);
+        Ok(match self.variant {
+            Some(v) => variant(v, map),
+            None => map,
+        })
+    }
+}
+
+impl ser::SerializeMap for Map {
+    type Ok = Data;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(Serializer::child(format!("{}[..]", self.path), key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value before serialize_key");
+        let path = match &key {
+            Data::Str(s) => format!("{}[{s:?}]", self.path),
+            Data::I64(n) => format!("{}[{n}]", self.path),
+            Data::U64(n) => format!("{}[{n}]", self.path),
+            _ => format!("{}[..]", self.path),
+        };
+        self.entries.push((key, Serializer::child(path, value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Data, Error> {
+        self.finish()
+    }
+}
+
+macro_rules! structure {
+    ($($trait:ident),+) => {$(
+        impl ser::$trait for Map {
+            type Ok = Data;
+            type Error = Error;
+
+            fn serialize_field<T: Serialize + ?Sized>(
+                &mut self,
+                key: &'static str,
+                value: &T,
+            ) -> Result<(), Error> {
+                let value = Serializer::child(format!("{}.{key}", self.path), value)?;
+                self.entries.push((Data::Str(key.into()), value));
+                Ok(())
+            }
+
+            fn end(self) -> Result<Data, Error> {
+                self.finish()
+            }
+        }
+    )+};
+}
+structure!(SerializeStruct, SerializeStructVariant);
+
+/// Deserializes like `D`, except that floats may also be the strings [`to_string`] writes them as.
+pub struct Deserializer<D>(pub D);
+
+macro_rules! forward {
+    ($($method:ident$(($($arg:ident: $ty:ty),*))?),+ $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, $($($arg: $ty,)*)? visitor: V) -> Result<V::Value, D::Error> {
+            self.0.$method($($($arg,)*)? Visit(visitor))
+        }
+    )+};
+}
+
+impl<'de, D: de::Deserializer<'de>> de::Deserializer<'de> for Deserializer<D> {
+    type Error = D::Error;
+
+    forward!(
+        deserialize_any,
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_option,
+        deserialize_unit,
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq,
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier,
+        deserialize_ignored_any,
+    );
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(Float(visitor))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(Float(visitor))
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(Int(visitor))
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(Int(visitor))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(MapOrPairs(visitor))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+/// Passes what it's given on to `V`, wrapping anything that deserializes further.
+struct Visit<V>(V);
+
+macro_rules! visit {
+    ($($method:ident($ty:ty)),+ $(,)?) => {$(
+        fn $method<E: de::Error>(self, v: $ty) -> Result<V::Value, E> {
+            self.0.$method(v)
+        }
+    )+};
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Visit<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    visit!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    );
+
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_unit()
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<V::Value, D::Error> {
+        self.0.visit_some(Deserializer(d))
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<V::Value, D::Error> {
+        self.0.visit_newtype_struct(Deserializer(d))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.0.visit_seq(Access(seq))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(Access(map))
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        self.0.visit_enum(Access(data))
+    }
+}
+
+/// A float, or a string that stands in for one.
+struct Float<V>(V);
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Float<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<V::Value, E> {
+        self.0.visit_i64(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<V::Value, E> {
+        self.0.visit_u64(v)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<V::Value, E> {
+        self.0.visit_f64(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<V::Value, E> {
+        // which also takes the floats of map keys
+        match v.parse::<f64>() {
+            Ok(f) => self.0.visit_f64(f),
+            Err(_) => self.0.visit_str(v),
+        }
+    }
+}
+
+/// A 128-bit integer, or a string of one.
+struct Int<V>(V);
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Int<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<V::Value, E> {
+        self.0.visit_i64(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<V::Value, E> {
+        self.0.visit_u64(v)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<V::Value, E> {
+        self.0.visit_f64(v)
+    }
+
+    // which also takes the integers of map keys
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<V::Value, E> {
+        match (v.parse::<u128>(), v.parse::<i128>()) {
+            (Ok(n), _) => self.0.visit_u128(n),
+            (_, Ok(n)) => self.0.visit_i128(n),
+            _ => self.0.visit_str(v),
+        }
+    }
+}
+
+/// A map, or a flat array of its keys and values.
+struct MapOrPairs<V>(V);
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for MapOrPairs<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(Access(map))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(Pairs(seq))
+    }
+}
+
+/// The entries of a map written as `[k, v, k, v]`.
+struct Pairs<A>(A);
+
+impl<'de, A: de::SeqAccess<'de>> de::MapAccess<'de> for Pairs<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        self.0.next_element_seed(Seed(seed))
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, A::Error> {
+        self.0
+            .next_element_seed(Seed(seed))?
+            .ok_or_else(|| de::Error::custom("the last key of a map has no value"))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint().map(|n| n / 2)
+    }
+}
+
+/// A [`de::SeqAccess`], [`de::MapAccess`], [`de::EnumAccess`] or [`de::VariantAccess`] whose values are deserialized with [`Deserializer`].
+struct Access<A>(A);
+
+/// Deserializes with [`Deserializer`].
+struct Seed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Seed<S> {
+    type Value = S::Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, d: D) -> Result<S::Value, D::Error> {
+        self.0.deserialize(Deserializer(d))
+    }
+}
+
+impl<'de, A: de::SeqAccess<'de>> de::SeqAccess<'de> for Access<A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        self.0.next_element_seed(Seed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+impl<'de, A: de::MapAccess<'de>> de::MapAccess<'de> for Access<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        self.0.next_key_seed(Seed(seed))
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, A::Error> {
+        self.0.next_value_seed(Seed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+impl<'de, A: de::EnumAccess<'de>> de::EnumAccess<'de> for Access<A> {
+    type Error = A::Error;
+    type Variant = Access<A::Variant>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self::Variant), A::Error> {
+        self.0
+            .variant_seed(Seed(seed))
+            .map(|(v, variant)| (v, Access(variant)))
+    }
+}
+
+impl<'de, A: de::VariantAccess<'de>> de::VariantAccess<'de> for Access<A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.0.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, A::Error> {
+        self.0.newtype_variant_seed(Seed(seed))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        self.0.tuple_variant(len, Visit(visitor))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        self.0.struct_variant(fields, Visit(visitor))
+    }
+}
+
+/// Deserialize a value from [`to_string`]'s json.
+pub fn from_slice<'de, T: de::Deserialize<'de>>(json: &'de [u8]) -> serde_json::Result<T> {
+    let mut de = serde_json::Deserializer::from_slice(json);
+    let value = T::deserialize(Deserializer(&mut de))?;
+    de.end()?;
+    Ok(value)
+}
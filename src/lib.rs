@@ -13,19 +13,126 @@
 //! ); // The program was compiled on 2023-11-16.
 //! ```
 //!
+//! ### Miri
+//!
+//! Put `#![miri]` at the start of a block (or set `EDG_MIRI=1` to do it for every block) to
+//! interpret the script under [Miri](https://github.com/rust-lang/miri) instead of running a native binary,
+//! so any undefined behaviour in your generator fails the build.
+//! The toolchain used is `nightly`, or whatever `EDG_MIRI_TOOLCHAIN` says.
+//! Miri needs the MIR of every dependency, so the crate has to be built by that same toolchain with `-Zalways-encode-mir`.
+//!
+//! ```ignore
+//! let x = edg::r! {
+//!     #![miri]
+//!     || -> u8 { unsafe { *[1u8, 2, 3].as_ptr().add(2) } }
+//! };
+//! ```
+//!
+//! ### WebAssembly
+//!
+//! With the `wasm` feature, `#![wasm]` (or `EDG_WASM=1`) compiles the script for `wasm32-wasip1` and runs it
+//! in an embedded [wasmtime](https://wasmtime.dev), which is deterministic, sandboxed, and gives the same result
+//! no matter which machine builds the crate.
+//! Your dependencies must also be built for `wasm32-wasip1`; point `EDG_WASM_DEPS` at their `deps` directory.
+//! Proc macros run on the host, so yours are used as they are, and the ones your dependencies were built with are
+//! looked for in that build's host `deps` directory (`target/debug/deps` for `target/wasm32-wasip1/debug/deps`).
+//!
+//! ### Toolchains
+//!
+//! Scripts are compiled by `rustc` (or `$RUSTC`). `#![toolchain("nightly-2024-06-01")]` at the start of a block
+//! (or `EDG_TOOLCHAIN`, for every block) compiles it with `rustup run <toolchain> rustc` instead,
+//! for generators that need another compiler than the crate.
+//! That compiler can't use the crate's dependencies, so build them with it too, and point `EDG_TOOLCHAIN_DEPS`
+//! at their `deps` directory.
+//!
+//! Like cargo, edg runs the compiler behind `RUSTC_WRAPPER` (and `RUSTC_WORKSPACE_WRAPPER`),
+//! so caches like sccache see the scripts too.
+//!
+//! ### `no_std`
+//!
+//! In `#![no_std]` crates (including ones that are only `no_std` through a `cfg_attr`), there is nothing to deserialize
+//! results with, so they are written out as literals instead, which works for primitives, arrays, tuples and
+//! `&'static str`s (anything else has to be `#![construct]`ed). That also makes them usable in `const`s and `static`s.
+//! The scripts are compiled for (and run on) the machine doing the build, so for a target without `std`, depend on
+//! edg with `default-features = false`, which leaves out the `std` feature and makes edg `no_std` too.
+//! Only its macros (and [`BuildInfo`]) are there then. The scripts need the rest, so build your dependencies for
+//! the host as well (with edg's `std`), and point `EDG_HOST_DEPS` at their `deps` directory
+//! (`target/debug/deps`, after a `cargo build --features edg/std` without `--target`).
+//!
+//! ```ignore
+//! #![no_std]
+//! static PRIMES: [u16; 4] = edg::r!(-> [u16; 4] { [2, 3, 5, 7] });
+//! ```
+//!
+//! ### Runtime fallback
+//!
+//! Some environments (docs.rs, sandboxed CI, cross builds) can't run `rustc` or binaries while expanding macros.
+//! With the `runtime-fallback` feature, nothing is evaluated at compile time: a block runs where it's written, every
+//! time it's reached, and evaluates to what it would have (going through serde if it's converted with `as`, and to its
+//! `fallback` if it panics). What needs compile time evaluation can't work like that:
+//!
+//! - blocks can't be used in `const`s or `static`s, which is an error about calling the non-`const` `at_run_time`;
+//! - [`static_!`] and [`table!`] statics are evaluated on first use, so instead of being the declared type, they
+//!   deref to (and compare like) it, and it has to be `Sync + Send`; `&S[..]` is a slice of an array one;
+//! - `#![stdin(..)]` and [`generate!`] are compile errors;
+//! - [`assert!`] checks nothing;
+//! - [`download!`] fetches nothing: it's what's cached, or else empty (or a placeholder, as under rustdoc).
+//!
+//! ### Documentation
+//!
+//! `cargo doc` expands macros inside rustdoc, where nothing can be compiled, so blocks aren't evaluated there.
+//! The same goes for `cargo check` and clippy, which only build the metadata of the crate's dependencies, so there
+//! is nothing for a script to be linked against.
+//! The value from the last normal build is used if there is one, and a placeholder otherwise:
+//! zeroes (or `false`, empty strs and slices, and `None`) where the type allows, so that consts and statics of them
+//! still evaluate. `#![construct]`ed ones can't be made up like that, so they need a normal build first.
+//! Likewise, [`download!`] doesn't fetch anything: downloads that aren't cached are empty (or placeholders).
+//! Doctests are compiled normally, so blocks in them are evaluated as usual.
+//!
+//! Normal builds are pipelined: cargo starts compiling a crate once its dependencies' metadata is ready,
+//! so a block may be expanded before the rlibs its script needs are written; it waits for them.
+//!
+//! ### Tests
+//!
+//! The script is always a normal binary, even when the crate is built with `--test` (by `cargo test`),
+//! so by default `cfg(test)` is not set inside it.
+//! Put `#![test]` at the start of a block (or set `EDG_TEST=1`) to give the script `cfg(test)` in test builds.
+//!
+//! ```
+//! let n = edg::r! {
+//!     #![test]
+//!     || -> u32 { if cfg!(test) { 10 } else { 1_000_000 } }
+//! };
+//! # _ = n;
+//! ```
+//!
+//! To test the blocks themselves (or snapshot what they expand to), the `edg-test` crate runs them outside of rustc.
+//!
+//! ### Failures
+//!
+//! A block that panics fails the build, with the panic's message, where it happened, and a backtrace.
+//! Macros built on edg can put that in their own terms with [`on_failure`], which also rephrases the `Err`s
+//! given to [`ok`].
+//!
 //! ### Limitations
 //!
 //! - Unlike Zig, `edg::r!` does not have access to the scope in which it is invoked, as
-//! the closure in `edg::r!` is run as its own script.
-//! - Unfortunately, as `serde` is not const, you cant have `const X: _ = edg::r! { .. }`.
-//! - Each block must be compiled sequentially.
+//!   the closure in `edg::r!` is run as its own script.
+//!   Consts with literal values can be copied in with `capture!(crate::NAME)`.
+//! - Unfortunately, as `serde` is not const, you cant have `const X: _ = edg::r! { .. }`,
+//!   unless the block is `#![construct]`ed (see [`ConstructTokens`]); [`static_!`] does that for you.
+//! - Top-level blocks are expanded one after another, but when the first one is, the crate's source is read for
+//!   the others, which start compiling in the background (`EDG_SPECULATE=0` turns that off).
+//!   The blocks nested in a block are compiled concurrently too, up to `EDG_JOBS` (or the number of cores) at once.
 //!
 //! ### How it works
 //!
 //! `edg::r!`:
 //!
-//! - adds serde_json::to_string to your code
-//! - creates a file `edg-{hash}.rs`, with your new code, in your target directory
+//! - adds serialization to your code (json, through `edg::__private`, so you don't need serde_json yourself;
+//!   NaN and the infinities, which json has no numbers for, and 128-bit integers too big for 64 bits are written as
+//!   strings, and maps whose keys can't be object keys as arrays of keys and values)
+//! - creates a file `edg-{hash}.rs`, with your new code, in `target/edg/<crate>-<version>`
 //! - compiles the file with `rustc`
 //! - executes the file
 //! - emits code to deserialize the json output.
@@ -34,185 +141,217 @@
 //!
 //! Much of the code is from the [`comptime`](https://crates.io/crates/comptime) crate.
 
-extern crate proc_macro;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::{
-    collections::hash_map::DefaultHasher,
-    fs::OpenOptions,
-    hash::{Hash, Hasher},
-    io::ErrorKind,
-    path::Path,
-    process::Command,
+mod buildinfo;
+#[cfg(feature = "std")]
+mod construct;
+#[cfg(feature = "std")]
+mod failure;
+#[cfg(feature = "std")]
+mod json;
+
+pub use buildinfo::BuildInfo;
+#[cfg(feature = "std")]
+pub use construct::ConstructTokens;
+#[cfg(feature = "std")]
+pub use edg_derive::ConstructTokens;
+pub use edg_macros::{
+    assert, bind, buildinfo, download, env, generate, json, memo, r, regex, static_, table,
+    transform,
 };
+#[cfg(feature = "std")]
+pub use failure::{ok, on_failure, Failure};
 
-use proc_macro::TokenStream;
-use quote::{quote, ToTokens};
-use syn::{ExprClosure, ReturnType};
-
-fn lock(dir: &Path) {
-    loop {
-        // no create_new stable :(
-        match OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create_new(true)
-            .open(dir.join("lock"))
-        {
-            Ok(_) => return,
-            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
-                std::hint::spin_loop();
-                continue;
-            }
-            Err(_) => panic!("unable to create lock"),
-        }
+/// A result that failed to deserialize, from a block with `on_error = result`.
+/// Without `std`, results are literals, which can't fail to.
+#[derive(Debug, Clone)]
+pub struct Error(
+    #[cfg(feature = "std")] String,
+    #[cfg(not(feature = "std"))] core::convert::Infallible,
+);
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
-fn unlock(dir: &Path) {
-    std::fs::remove_file(dir.join("lock")).expect("unable to unlock");
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for Error {
+    fn fmt(&self, _: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {}
+    }
 }
 
-#[proc_macro]
-/// Run a closure at compile time.
-/// This closure is completely isolated.
-/// You may return any data structure that implements [`serde::Serialize`](https://docs.rs/serde/latest/serde/trait.Serialize.html) and [`serde::Deserialize`](https://docs.rs/serde/latest/serde/trait.Deserialize.html).
-///
-/// ```
-/// let rand = edg::r! { || -> i32 {
-/// # mod rand { pub fn random() -> i32 { 4 } }
-///     rand::random()
-/// } };
-/// ```
-pub fn r(input: TokenStream) -> TokenStream {
-    let out_dir = std::env::current_dir().map_or("/tmp".into(), |p| p.join("target"));
-    macro_rules! err {
-        ($fstr:literal$(,)? $( $arg:expr ),*) => {{
-            unlock(&out_dir);
-            let compile_error = format!($fstr, $($arg),*);
-            return TokenStream::from(quote!(compile_error!(#compile_error)));
-        }};
-    }
-    lock(&out_dir);
-
-    let args: Vec<_> = std::env::args().collect();
-
-    let input = syn::parse_macro_input!(input as ExprClosure);
-
-    let ty = match input.output {
-        ReturnType::Default => err!("specify return type of closure"),
-        ReturnType::Type(_, t) => t,
-    };
-
-    let code = input.body.to_token_stream().to_string();
-    let mut hasher = DefaultHasher::new();
-    code.hash(&mut hasher);
-    let hash = hasher.finish();
-
-    let file = out_dir.join(format!("edg-{hash}.rs"));
-    std::fs::write(
-        &file,
-        format!(
-            r#"fn main() {{
-                    let res: {} = 
-{code}
-; // surely nobody will main()
-                    let ser = serde_json::to_string(&res).expect("serialization failed");
-                    print!("{{ser}}");
-                }}"#,
-            ty.to_token_stream().to_string()
-        ),
-    )
-    .expect("could not write file");
-
-    let mut rustc = Command::new("rustc");
-    rustc.args(filter_rustc_args(&args));
-    rustc.args(["--crate-name", "edg_bin"]);
-    rustc.args(["--crate-type", "bin"]);
-    rustc.args(["--out-dir".as_ref(), out_dir.as_os_str()]);
-    rustc.args(merge_externs(&args));
-    rustc.arg(file.to_str().unwrap());
-
-    let compile_output = rustc.output().expect("could not invoke rustc");
-    if !compile_output.status.success() {
-        err!(
-            "could not compile comptime expr:\n\n{}\n",
-            String::from_utf8(compile_output.stderr).unwrap()
-        );
+impl core::error::Error for Error {}
+
+#[doc(hidden)]
+/// Used by the expansions. Not public API.
+pub mod __private {
+    #[cfg(feature = "std")]
+    pub use std_::*;
+
+    /// A block that isn't evaluated (under rustdoc or `cargo check`), typed, so that the code after it isn't unreachable.
+    pub const fn placeholder<T>() -> T {
+        panic!("edg blocks are not evaluated when only metadata is built (by rustdoc or `cargo check`)")
     }
-    print!("{}", String::from_utf8(compile_output.stdout).unwrap());
-    print!("{}", String::from_utf8(compile_output.stderr).unwrap());
-
-    let extra = args
-        .iter()
-        .find(|a| a.starts_with("extra-filename="))
-        .map(|ef| ef.split('=').nth(1).unwrap())
-        .unwrap_or_default();
-    let out = out_dir.join(format!("edg_bin{extra}"));
-
-    let comptime_output = Command::new(&out)
-        .output()
-        .expect("could not invoke edg_bin");
-
-    if !comptime_output.status.success() {
-        err!(
-            "could not run comptime expr:\n\n{}\n",
-            String::from_utf8(comptime_output.stderr).unwrap()
-        );
+
+    /// [`placeholder`], of the type `f` returns (for blocks that don't name theirs).
+    pub const fn placeholder_of<T>(f: impl FnOnce() -> T) -> T {
+        core::mem::forget(f);
+        placeholder()
     }
 
-    let comptime_expr = if let Ok(output) = String::from_utf8(comptime_output.stdout) {
-        output
-    } else {
-        err!("comptime expr output was not utf8")
-    };
+    /// What scripts, and the expansions that deserialize their results, use.
+    #[cfg(feature = "std")]
+    mod std_ {
+        use crate::{failure, json, Error, Failure};
+        use serde::{de::DeserializeOwned, Serialize};
+        use std::sync::LazyLock;
 
-    _ = std::fs::remove_file(file);
-    _ = std::fs::remove_file(out);
+        pub use serde_json;
 
-    unlock(&out_dir);
+        /// Serialize a block's result, in the script.
+        pub fn to_json<T: Serialize + ?Sized>(value: &T) -> String {
+            json::to_string(value).unwrap_or_else(|e| panic!("could not serialize the result: {e}"))
+        }
 
-    quote!(::serde_json::from_str::<#ty>(#comptime_expr).expect(&format!("deser of expr ({}) failed (bug in `Deserialize` impl)", #comptime_expr))).into()
-}
+        /// Deserialize a block's result.
+        pub fn from_json<T: DeserializeOwned>(json: &str) -> T {
+            json::from_slice(json.as_bytes()).unwrap_or_else(|e| {
+                panic!("deser of expr ({json}) failed (bug in `Deserialize` impl): {e}")
+            })
+        }
 
-fn filter_rustc_args(args: &[String]) -> Vec<&str> {
-    let mut rustc_args = Vec::with_capacity(args.len());
-    let mut skip = true;
-    for arg in args {
-        if &**arg == "-" {
-            continue;
-        }
-        if skip {
-            skip = false;
-            continue;
-        }
-        if arg == "--crate-type" || arg == "--crate-name" || arg == "--extern" || arg == "-o" {
-            skip = true;
-        } else if arg.ends_with(".rs")
-            || arg == "--test"
-            || arg == "rustc"
-            || arg.starts_with("--emit")
-        {
-            continue;
-        } else {
-            rustc_args.push(&**arg);
+        /// Deserialize a block's result, for `on_error = default | result`.
+        pub fn try_from_json<T: DeserializeOwned>(json: &str) -> Result<T, Error> {
+            json::from_slice(json.as_bytes()).map_err(|e| {
+                Error(format!(
+                    "deser of expr failed (bug in `Deserialize` impl): {e}"
+                ))
+            })
+        }
+
+        /// Deserialize a `#![compress]`ed result.
+        pub fn from_compressed<T: DeserializeOwned>(data: &[u8]) -> T {
+            try_from_compressed(data).unwrap_or_else(|e| panic!("{e}"))
+        }
+
+        /// Deserialize a `#![compress]`ed result, for `on_error = default | result`.
+        pub fn try_from_compressed<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+            json::from_slice(&inflate(data)).map_err(|e| {
+                Error(format!(
+                    "deser of compressed expr failed (bug in `Deserialize` impl): {e}"
+                ))
+            })
+        }
+
+        /// Evaluate a block where it is, for the `runtime-fallback` feature (which is why it's not a `const fn`).
+        pub fn at_run_time<T>(f: impl FnOnce() -> T) -> T {
+            f()
+        }
+
+        /// [`at_run_time`], with what to use instead if the block panics.
+        pub fn at_run_time_or<T>(f: impl FnOnce() -> T, fallback: impl FnOnce() -> T) -> T {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|_| fallback())
+        }
+
+        /// A `static` that is evaluated on first use, for the `runtime-fallback` feature.
+        /// It derefs to (and compares like) what it holds.
+        pub struct Lazy<T>(LazyLock<T>);
+
+        impl<T> Lazy<T> {
+            pub const fn new(f: fn() -> T) -> Self {
+                Self(LazyLock::new(f))
+            }
+        }
+
+        impl<T> std::ops::Deref for Lazy<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
         }
-    }
-    rustc_args
-}
 
-fn merge_externs(args: &[String]) -> Vec<&str> {
-    let mut found = false;
-    let mut ret = vec![];
-    for arg in args {
-        match &**arg {
-            arg if found => {
-                found = false;
-                ret.push("--extern");
-                ret.push(arg);
+        impl<T: PartialEq<U>, U> PartialEq<U> for Lazy<T> {
+            fn eq(&self, other: &U) -> bool {
+                **self == *other
             }
-            "--extern" => found = true,
-            _ => continue,
+        }
+
+        impl<T: std::fmt::Debug> std::fmt::Debug for Lazy<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                (**self).fmt(f)
+            }
+        }
+
+        /// Write a script's output to stdout.
+        /// Big outputs are deflated, behind a nul byte (which json can't start with).
+        pub fn emit(out: &str) {
+            use std::io::Write;
+            let mut stdout = std::io::stdout().lock();
+            if out.len() < 1 << 16 {
+                stdout.write_all(out.as_bytes())
+            } else {
+                stdout.write_all(&[0]).unwrap();
+                stdout.write_all(&miniz_oxide::deflate::compress_to_vec(out.as_bytes(), 6))
+            }
+            .unwrap()
+        }
+
+        /// Report panics (with a backtrace, and what [`on_failure`](crate::on_failure) made of them) as a line of json,
+        /// which the macro turns into a compile error.
+        pub fn hook() {
+            std::panic::set_hook(Box::new(|info| {
+                let (file, line, column) = info
+                    .location()
+                    .map_or(("", 0, 0), |l| (l.file(), l.line(), l.column()));
+                let err = info.payload().downcast_ref::<failure::Errored>();
+                let message = match err {
+                    Some(e) => &e.0,
+                    None => info.payload_as_str().unwrap_or("Box<dyn Any>"),
+                };
+                let failure = Failure {
+                    message,
+                    err: err.is_some(),
+                    location: info.location(),
+                };
+                let panic = serde_json::json!({
+                    "message": message,
+                    "err": err.is_some(),
+                    "explained": failure::explain(&failure),
+                    "file": file,
+                    "line": line,
+                    "column": column,
+                    "backtrace": std::backtrace::Backtrace::force_capture().to_string(),
+                });
+                eprintln!("edg::panic {panic}");
+            }));
+        }
+
+        /// What `edg::env!`'s validators (and `edg::assert!`s) may return.
+        pub trait Valid {
+            fn valid(self) -> Result<(), String>;
+        }
+
+        impl Valid for bool {
+            fn valid(self) -> Result<(), String> {
+                self.then_some(()).ok_or_else(|| "returned false".into())
+            }
+        }
+
+        impl<E: std::fmt::Display> Valid for Result<(), E> {
+            fn valid(self) -> Result<(), String> {
+                self.map_err(|e| e.to_string())
+            }
+        }
+
+        /// Decompress a `#![compress]`ed payload.
+        pub fn inflate(data: &[u8]) -> Vec<u8> {
+            miniz_oxide::inflate::decompress_to_vec(data).expect("edg payload is corrupt")
         }
     }
-    ret
 }
@@ -0,0 +1,96 @@
+//! Runs [edg](https://docs.rs/edg) blocks outside of rustc, so that they (and edg itself) can be tested like
+//! any other code. A block goes through what it would under `edg::r!`: its script is written out, compiled
+//! (against the dependencies of the test that's running) and run, and you get the expansion, or the result, back.
+//!
+//! ```no_run
+//! let h = edg_test::Harness::new("src/lib.rs");
+//! assert_eq!(h.expand("-> u32 { 1 + 2 }"), "3u32");
+//! assert_eq!(h.eval("-> Vec<u8> { vec![1, 2] }").unwrap(), "[1,2]");
+//! ```
+use std::path::PathBuf;
+
+/// Where blocks are run from: a stand-in for the rustc they'd be expanded in.
+pub struct Harness {
+    args: Vec<String>,
+    out_dir: PathBuf,
+    /// the test's `target/<profile>/deps`
+    deps: PathBuf,
+}
+
+impl Harness {
+    /// For blocks in `file` (which is also taken to be the crate root, for `capture!`),
+    /// that can use edg, and the crates it depends on.
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        let deps = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.to_owned()))
+            .unwrap_or_default();
+        let target = deps.ancestors().nth(2).unwrap_or(&deps);
+        let args = vec![
+            "rustc".into(),
+            "--edition=2021".into(),
+            format!("-Ldependency={}", deps.display()),
+            file.into().display().to_string(),
+        ];
+        Self {
+            args,
+            out_dir: target.join("edg").join("edg-test"),
+            deps,
+        }
+        .with("edg")
+    }
+
+    /// Let blocks use another of the test's dependencies (the newest build of it, if there are several).
+    pub fn with(mut self, krate: &str) -> Self {
+        let name = krate.replace('-', "_");
+        let prefix = format!("lib{name}-");
+        let rlib = std::fs::read_dir(&self.deps)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_str()
+                    .is_some_and(|f| f.starts_with(&prefix) && f.ends_with(".rlib"))
+            })
+            .max_by_key(|e| e.metadata().and_then(|m| m.modified()).ok());
+        self.args.push("--extern".into());
+        self.args.push(match rlib {
+            Some(rlib) => format!("{name}={}", rlib.path().display()),
+            // let rustc look for it
+            None => name,
+        });
+        self
+    }
+
+    /// Give rustc another argument (`.arg("--cfg").arg("feature=\"big\"")` turns a feature on, say).
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    fn hosted<T>(&self, f: impl FnOnce() -> T) -> T {
+        let host = edg_core::Host {
+            args: self.args.clone(),
+            out_dir: self.out_dir.clone(),
+        };
+        let outer = edg_core::HOST.replace(Some(host));
+        let out = f();
+        edg_core::HOST.set(outer);
+        out
+    }
+
+    /// What `edg::r!` expands `input` to (a `compile_error!`, if it fails).
+    pub fn expand(&self, input: &str) -> String {
+        match input.parse() {
+            Ok(input) => self.hosted(|| edg_core::r(input)).to_string(),
+            Err(e) => format!("could not lex the block: {e}"),
+        }
+    }
+
+    /// Run the block, returning what it produced (its result's json, usually), or what `edg::r!` would fail with.
+    pub fn eval(&self, input: &str) -> Result<String, String> {
+        let input = input.parse().map_err(|e| format!("{e:?}"))?;
+        self.hosted(|| edg_core::eval(input))
+    }
+}
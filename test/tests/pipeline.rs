@@ -0,0 +1,111 @@
+//! edg's pipeline, run by the harness.
+use edg_test::Harness;
+
+fn harness() -> Harness {
+    Harness::new("tests/pipeline.rs")
+}
+
+#[test]
+fn scalars_are_literals() {
+    assert_eq!(harness().expand("-> u32 { (1..=10).sum() }"), "55u32");
+    assert_eq!(harness().expand("-> i8 { -3 }"), "(- 3i8)");
+    assert_eq!(harness().expand("-> f64 { f64::NAN }"), "f64 :: NAN");
+}
+
+#[test]
+fn results() {
+    let h = harness();
+    assert_eq!(h.eval("-> Vec<u8> { vec![1, 2] }").unwrap(), "[1,2]");
+    assert_eq!(h.eval("|| (1u8, \"x\")").unwrap(), r#"[1,"x"]"#);
+    assert_eq!(
+        h.eval("-> std::collections::BTreeMap<(u8, u8), u8> { [((1, 2), 3)].into() }")
+            .unwrap(),
+        "[[1,2],3]"
+    );
+    assert_eq!(
+        h.eval("-> u128 { u128::MAX }").unwrap(),
+        format!("\"{}\"", u128::MAX)
+    );
+}
+
+#[test]
+fn deserialization() {
+    let out = harness().expand("-> Vec<u8> { vec![1] }");
+    assert!(
+        out.contains(":: edg :: __private :: from_json :: < Vec < u8 > > (\"[1]\")"),
+        "{out}"
+    );
+    let out = harness().expand("-> Vec<u8> { vec![1] }, on_error = result");
+    assert!(out.contains("try_from_json"), "{out}");
+}
+
+#[test]
+fn panics() {
+    let e = harness().eval("-> u8 { panic!(\"no\") }").unwrap_err();
+    assert!(
+        e.starts_with("comptime expression panicked at tests/pipeline.rs:"),
+        "{e}"
+    );
+    assert!(e.contains(": no"), "{e}");
+    let out = harness().expand("-> u8 { panic!(\"no\") }");
+    assert!(out.starts_with(":: core :: compile_error !"), "{out}");
+}
+
+#[test]
+fn explained_failures() {
+    let h = harness();
+    let e = h
+        .eval("-> u8 { edg::ok(\"x\".parse::<u8>()) }")
+        .unwrap_err();
+    assert!(
+        e.starts_with("comptime expression failed at tests/pipeline.rs:"),
+        "{e}"
+    );
+    let explained = "-> u8 {
+        edg::on_failure(|f| Some(format!(\"bad number in x.txt: {}\", f.message)));
+        edg::ok(\"x\".parse::<u8>())
+    }";
+    assert_eq!(
+        h.eval(explained).unwrap_err(),
+        "bad number in x.txt: invalid digit found in string"
+    );
+}
+
+#[test]
+fn compile_errors() {
+    let e = harness().eval("-> u8 { \"not a u8\" }").unwrap_err();
+    assert!(e.starts_with("could not compile comptime expr"), "{e}");
+    assert!(e.contains("mismatched types"), "{e}");
+}
+
+#[test]
+fn cfg() {
+    let h = harness().arg("--cfg").arg("feature=\"big\"");
+    assert_eq!(
+        h.expand("#![cfg(feature = \"big\")] -> u8 { 1 }, else = 2"),
+        "1u8"
+    );
+    assert_eq!(
+        harness().expand("#![cfg(feature = \"big\")] -> u8 { 1 }, else = 2"),
+        "{ let edg : u8 = 2 ; edg }"
+    );
+}
+
+#[test]
+fn target_cfg() {
+    let block = "#![cfg(not(all(target_arch = \"wasm32\", target_pointer_width = \"32\")))] -> u8 { 1 }, else = 2";
+    assert_eq!(harness().expand(block), "1u8");
+    assert_eq!(
+        harness()
+            .arg("--target")
+            .arg("wasm32-unknown-unknown")
+            .expand(block),
+        "{ let edg : u8 = 2 ; edg }"
+    );
+    let block = "#![cfg(debug_assertions)] -> u8 { 1 }, else = 2";
+    assert_eq!(harness().expand(block), "1u8");
+    assert_eq!(
+        harness().arg("-O").expand(block),
+        "{ let edg : u8 = 2 ; edg }"
+    );
+}
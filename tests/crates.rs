@@ -0,0 +1,198 @@
+//! Crates that use edg, built by cargo like any other.
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Where the fixtures are built, apart from edg's own `target` (which cargo holds while it runs the tests).
+fn target() -> PathBuf {
+    root().join("target").join("edg-fixtures").join("target")
+}
+
+/// The dependency on this edg, with `keys` too.
+fn edg(keys: &str) -> String {
+    format!("edg = {{ path = {:?}{keys} }}", root())
+}
+
+/// Write out a crate named `name`, with `dependencies` and `lib` as its root.
+fn fixture(name: &str, dependencies: &str, lib: &str) -> PathBuf {
+    let dir = root().join("target").join("edg-fixtures").join(name);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    let manifest = format!(
+        "[package]\nname = \"{name}\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n\
+         [dependencies]\n{dependencies}\n\n[workspace]\n"
+    );
+    std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    std::fs::write(dir.join("src").join("lib.rs"), lib).unwrap();
+    // the versions edg is tested with (which are already downloaded), if they've been locked;
+    // otherwise cargo picks from what's downloaded itself (`--offline`)
+    if root().join("Cargo.lock").exists() {
+        std::fs::copy(root().join("Cargo.lock"), dir.join("Cargo.lock")).unwrap();
+    } else {
+        _ = std::fs::remove_file(dir.join("Cargo.lock"));
+    }
+    dir
+}
+
+/// Run cargo in `dir`, building into `target`, failing with its output if it does (and returning it otherwise).
+fn cargo(dir: &Path, target: &Path, args: &[&str], env: &[(&str, &Path)]) -> String {
+    let out = Command::new(env!("CARGO"))
+        .args(args)
+        .arg("--offline")
+        .arg("--target-dir")
+        .arg(target)
+        .current_dir(dir)
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "cargo {args:?} failed:\n{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let out = Command::new("git")
+        .args(["-c", "user.name=edg", "-c", "user.email=edg@localhost"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "git {args:?} failed:\n{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+fn host() -> String {
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".into());
+    let out = Command::new(rustc).arg("-vV").output().unwrap();
+    String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .find_map(|l| l.strip_prefix("host: ").map(str::to_owned))
+        .unwrap()
+}
+
+#[test]
+fn no_std() {
+    let dir = fixture(
+        "no-std",
+        &edg(", default-features = false"),
+        "#![no_std]\n\
+         pub static PRIMES: [u16; 4] = edg::r!(-> [u16; 4] { [2, 3, 5, 7] });\n\
+         pub const NAME: &str = edg::r!(-> &'static str { \"edg\" });\n\
+         pub const HALF: Result<f32, edg::Error> = edg::r!(-> f32 { 0.5 }, on_error = result);\n",
+    );
+    // the scripts are built against a build of edg with `std`, for the host (kept apart, so it's the only edg there)
+    let host_target = root().join("target").join("edg-fixtures").join("host");
+    cargo(&dir, &host_target, &["build", "--features", "edg/std"], &[]);
+    let deps = host_target.join("debug").join("deps");
+    cargo(
+        &dir,
+        &target(),
+        &["build", "--target", &host()],
+        &[("EDG_HOST_DEPS", &deps)],
+    );
+}
+
+#[test]
+fn check_before_building() {
+    let dir = fixture(
+        "check",
+        &edg(""),
+        "edg::table!(pub SQUARES: [u32; 16] = |i| (i * i) as u32);\n\
+         edg::static_!(pub NAMES: (&str, [char; 2], &[u8]) = || (\"edg\", ['a', 'b'], &[1, 2]));\n\
+         edg::static_!(pub LIMIT: Option<u8> = || Some(3));\n\
+         pub const SUM: u64 = edg::r!(-> u64 { 1 + 2 });\n\
+         pub const BYTES: &[u8] = edg::r!(-> impl Iterator<Item = u8> { 0..4 });\n\
+         pub fn half() -> f64 {\n    edg::r!(|| 0.5f64) * 2.0\n}\n\
+         pub static PAGE: &str = edg::download!(\"https://edg.invalid/page\" as &str);\n\
+         pub const COUNT: u32 = edg::download!(\"https://edg.invalid/count\" as u32);\n",
+    );
+    // nothing has been built (so there's nothing recorded), and nothing will be (or downloaded)
+    _ = std::fs::remove_dir_all(target().join("edg").join("check-0.0.0"));
+    cargo(&dir, &target(), &["check"], &[]);
+}
+
+#[test]
+fn dependencies() {
+    let dep = fixture(
+        "dep",
+        &edg(""),
+        "pub const N: u32 = edg::r!(-> u32 { 6 * 7 });\n",
+    );
+    let dir = fixture(
+        "dependent",
+        &format!("{}\ndep = {{ path = {dep:?} }}", edg("")),
+        "pub const N: u32 = dep::N;\n",
+    );
+    _ = std::fs::remove_dir_all(dep.join("target"));
+    _ = std::fs::remove_dir_all(target().join("edg").join("dep-0.0.0"));
+    cargo(&dir, &target(), &["build"], &[]);
+    // it's built where its source is (like one from a registry), but its outputs go in the target directory
+    assert!(!dep.join("target").exists());
+    assert!(target().join("edg").join("dep-0.0.0").is_dir());
+}
+
+#[test]
+fn reused_binaries() {
+    let lib = "pub const N: u32 = edg::r!(-> u32 { 6 * 7 });\n";
+    let dir = fixture("reused", &edg(""), lib);
+    let out = target().join("edg").join("reused-0.0.0");
+    _ = std::fs::remove_dir_all(&out);
+    let cached = || {
+        let stats = std::fs::read_dir(&out)
+            .unwrap()
+            .flatten()
+            .find(|e| e.file_name().to_string_lossy().starts_with("stats-"))
+            .unwrap();
+        let stats = std::fs::read_to_string(stats.path()).unwrap();
+        stats.contains("\"cached\":true")
+    };
+    cargo(&dir, &target(), &["build"], &[]);
+    assert!(!cached());
+    // rebuilt, but the block is the same
+    std::fs::write(dir.join("src").join("lib.rs"), format!("{lib}\n")).unwrap();
+    cargo(&dir, &target(), &["build"], &[]);
+    assert!(cached());
+}
+
+#[test]
+fn buildinfo_in_a_worktree() {
+    let dir = fixture(
+        "repository",
+        &edg(""),
+        "pub const BUILD: edg::BuildInfo = edg::buildinfo!();\n",
+    );
+    let tree = dir.with_file_name("worktree");
+    _ = std::fs::remove_dir_all(dir.join(".git"));
+    _ = std::fs::remove_dir_all(&tree);
+    git(&dir, &["init", "-q", "-b", "main"]);
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-qm", "a"]);
+    git(
+        &dir,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "tree",
+            tree.to_str().unwrap(),
+        ],
+    );
+    // its branch is packed, and then committed to
+    git(&dir, &["pack-refs", "--all"]);
+    cargo(&tree, &target(), &["build"], &[]);
+    git(&tree, &["commit", "-q", "--allow-empty", "-m", "b"]);
+    let out = cargo(&tree, &target(), &["build"], &[]);
+    assert!(out.contains("Compiling repository"), "{out}");
+}
@@ -0,0 +1,100 @@
+//! Floats come out of blocks with the same bits they went in with, whichever way they are embedded.
+// the digits past what the float can hold are the point (of -2.2250738585072009e-308)
+#![allow(clippy::excessive_precision)]
+
+const F64: [f64; 10] = [
+    0.0,
+    -0.0,
+    0.1,
+    1.0 / 3.0,
+    f64::MIN_POSITIVE,
+    5e-324,
+    f64::MAX,
+    f64::MIN,
+    f64::EPSILON,
+    -2.2250738585072009e-308,
+];
+
+const F32: [f32; 7] = [
+    0.0,
+    -0.0,
+    0.1,
+    f32::MIN_POSITIVE,
+    1e-45,
+    f32::MAX,
+    16777217.0,
+];
+
+fn bits64(v: &[f64]) -> Vec<u64> {
+    v.iter().map(|f| f.to_bits()).collect()
+}
+
+fn bits32(v: &[f32]) -> Vec<u32> {
+    v.iter().map(|f| f.to_bits()).collect()
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(edg::r!(-> f64 { -0.0 }).to_bits(), (-0.0f64).to_bits());
+    assert_eq!(edg::r!(-> f64 { 5e-324 }).to_bits(), 5e-324f64.to_bits());
+    assert_eq!(
+        edg::r!(-> f64 { 0.1 + 0.2 }).to_bits(),
+        (0.1f64 + 0.2).to_bits()
+    );
+    assert_eq!(edg::r!(-> f32 { -0.0 }).to_bits(), (-0.0f32).to_bits());
+    assert_eq!(edg::r!(-> f32 { 1e-45 }).to_bits(), 1e-45f32.to_bits());
+    // no type to go by
+    assert_eq!(edg::r!(|| -0.0f64).to_bits(), (-0.0f64).to_bits());
+}
+
+#[test]
+fn deserialized() {
+    let v = edg::r!(-> Vec<f64> {
+        vec![0.0, -0.0, 0.1, 1.0 / 3.0, f64::MIN_POSITIVE, 5e-324, f64::MAX, f64::MIN, f64::EPSILON, -2.2250738585072009e-308]
+    });
+    assert_eq!(bits64(&v), bits64(&F64));
+    let v = edg::r!(-> Vec<f32> {
+        vec![0.0, -0.0, 0.1, f32::MIN_POSITIVE, 1e-45, f32::MAX, 16777217.0]
+    });
+    assert_eq!(bits32(&v), bits32(&F32));
+}
+
+#[test]
+fn literals() {
+    edg::static_!(S: [f64; 10] = || {
+        [0.0, -0.0, 0.1, 1.0 / 3.0, f64::MIN_POSITIVE, 5e-324, f64::MAX, f64::MIN, f64::EPSILON, -2.2250738585072009e-308]
+    });
+    assert_eq!(bits64(&S[..]), bits64(&F64));
+    edg::static_!(S32: [f32; 7] = || [0.0, -0.0, 0.1, f32::MIN_POSITIVE, 1e-45, f32::MAX, 16777217.0]);
+    assert_eq!(bits32(&S32[..]), bits32(&F32));
+    let v: &[f64] = edg::r!(-> impl Iterator<Item = f64> {
+        [0.0, -0.0, 0.1, 1.0 / 3.0, f64::MIN_POSITIVE, 5e-324, f64::MAX, f64::MIN, f64::EPSILON, -2.2250738585072009e-308].into_iter()
+    });
+    assert_eq!(bits64(v), bits64(&F64));
+}
+
+#[test]
+fn compressed() {
+    let v = edg::r! { #![compress] -> Vec<f64> {
+        (0..1000).map(|i| f64::from_bits(0x3ff0_0000_0000_0000 + i * 0x0001_2345_6789)).collect()
+    } };
+    let expected = (0..1000)
+        .map(|i| f64::from_bits(0x3ff0_0000_0000_0000 + i * 0x0001_2345_6789))
+        .collect::<Vec<_>>();
+    assert_eq!(bits64(&v), bits64(&expected));
+}
+
+#[test]
+fn non_finite() {
+    let v = edg::r!(-> Vec<f64> { vec![f64::NAN, f64::INFINITY, f64::NEG_INFINITY] });
+    assert!(v[0].is_nan());
+    assert_eq!(v[1..], [f64::INFINITY, f64::NEG_INFINITY]);
+    assert!(edg::r!(-> f32 { f32::NAN }).is_nan());
+    assert_eq!(edg::r!(-> f32 { -f32::INFINITY }), f32::NEG_INFINITY);
+    edg::static_!(S: [f64; 2] = || [f64::INFINITY, f64::NAN]);
+    assert!(S[0] == f64::INFINITY && S[1].is_nan());
+    let m = edg::r!(-> std::collections::BTreeMap<String, (f32, f64)> {
+        [("x".into(), (f32::NAN, f64::INFINITY))].into()
+    });
+    assert!(m["x"].0.is_nan() && m["x"].1 == f64::INFINITY);
+}
@@ -0,0 +1,77 @@
+//! 128-bit integers come out of blocks exactly, however big they are.
+
+use std::collections::BTreeMap;
+
+const U: [u128; 5] = [
+    0,
+    u64::MAX as u128,
+    u64::MAX as u128 + 1,
+    u128::MAX - 1,
+    u128::MAX,
+];
+const I: [i128; 6] = [
+    i128::MIN,
+    i128::MIN + 1,
+    i64::MIN as i128 - 1,
+    -1,
+    i64::MAX as i128 + 1,
+    i128::MAX,
+];
+
+#[test]
+fn scalars() {
+    assert_eq!(edg::r!(-> u128 { u128::MAX }), u128::MAX);
+    assert_eq!(
+        edg::r!(-> u128 { u64::MAX as u128 + 1 }),
+        u64::MAX as u128 + 1
+    );
+    assert_eq!(edg::r!(-> i128 { i128::MIN }), i128::MIN);
+    assert_eq!(edg::r!(-> i128 { i128::MAX }), i128::MAX);
+    assert_eq!(edg::r!(-> i128 { -1 }), -1);
+    // usable in const contexts too (but not when blocks are evaluated at run time)
+    #[cfg(not(feature = "runtime-fallback"))]
+    {
+        const N: u128 = edg::r!(-> u128 { u128::MAX });
+        assert_eq!(N, u128::MAX);
+    }
+    assert_eq!(edg::r!(|| i128::MIN), i128::MIN);
+}
+
+#[test]
+fn deserialized() {
+    let v = edg::r!(-> Vec<u128> {
+        vec![0, u64::MAX as u128, u64::MAX as u128 + 1, u128::MAX - 1, u128::MAX]
+    });
+    assert_eq!(v, U);
+    let v = edg::r!(-> Vec<i128> {
+        vec![i128::MIN, i128::MIN + 1, i64::MIN as i128 - 1, -1, i64::MAX as i128 + 1, i128::MAX]
+    });
+    assert_eq!(v, I);
+    let v = edg::r!(-> (Option<u128>, Result<i128, ()>) { (Some(u128::MAX), Ok(i128::MIN)) });
+    assert_eq!(v, (Some(u128::MAX), Ok(i128::MIN)));
+    let v = edg::r! { #![compress] -> Vec<u128> { vec![u128::MAX; 100] } };
+    assert_eq!(v, [u128::MAX; 100]);
+}
+
+#[test]
+fn keys() {
+    let m = edg::r!(-> std::collections::BTreeMap<u128, i128> {
+        [(u128::MAX, i128::MIN), (1, -1)].into()
+    });
+    assert_eq!(m, BTreeMap::from([(u128::MAX, i128::MIN), (1, -1)]));
+    let m =
+        edg::r!(-> std::collections::BTreeMap<(i128, u8), ()> { [((i128::MIN, 0), ())].into() });
+    assert_eq!(m, BTreeMap::from([((i128::MIN, 0), ())]));
+}
+
+#[test]
+fn literals() {
+    edg::static_!(S: [u128; 5] = || [0, u64::MAX as u128, u64::MAX as u128 + 1, u128::MAX - 1, u128::MAX]);
+    assert_eq!(S, U);
+    edg::static_!(T: [i128; 6] = || {
+        [i128::MIN, i128::MIN + 1, i64::MIN as i128 - 1, -1, i64::MAX as i128 + 1, i128::MAX]
+    });
+    assert_eq!(T, I);
+    let v: &[i128] = edg::r!(-> impl Iterator<Item = i128> { [i128::MIN, i128::MAX].into_iter() });
+    assert_eq!(v, [i128::MIN, i128::MAX]);
+}